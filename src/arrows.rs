@@ -0,0 +1,85 @@
+use crate::{Point, Vector2};
+
+// how the arrow length is derived from the field strength
+#[derive(Deserialize, Debug, Copy, Clone, Default)]
+pub enum Scaling {
+  #[default]
+  Linear,
+  // linear up to the given field strength, constant above it
+  Clamped(f64),
+  // ln(1 + |E|), keeps arrows far from the charges visible
+  Logarithmic,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Arrow {
+  position: Point,
+  // unit vector along the field, zero where the field vanishes
+  direction: Vector2,
+  magnitude: f64,
+  // `magnitude` after `Scaling` is applied
+  length: f64,
+}
+
+impl Scaling {
+  fn apply(self, magnitude: f64) -> f64 {
+    match self {
+      Scaling::Linear =>
+        magnitude,
+      Scaling::Clamped(max) =>
+        magnitude.min(max),
+      Scaling::Logarithmic =>
+        magnitude.ln_1p(),
+    }
+  }
+}
+
+pub fn arrow(position: Point, field: Vector2, scaling: Scaling) -> Arrow {
+  let magnitude = vecmath::vec2_len(field);
+  Arrow {
+    position,
+    direction: crate::field_direction(field).unwrap_or([0.0, 0.0]),
+    magnitude,
+    length: scaling.apply(magnitude),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::grid::Grid;
+  use crate::tests::charge;
+  use crate::Config;
+
+  #[test]
+  fn arrows_point_along_the_field() {
+    let along = arrow([1.0, 2.0], [3.0, -4.0], Scaling::Linear);
+    assert_eq!(along.position, [1.0, 2.0]);
+    assert!(crate::distance(along.direction, [0.6, -0.8]) < 1e-12);
+    assert_eq!((along.magnitude, along.length), (5.0, 5.0));
+    let still = arrow([1.0, 2.0], [0.0, 0.0], Scaling::Linear);
+    assert_eq!((still.direction, still.magnitude), ([0.0, 0.0], 0.0));
+  }
+
+  #[test]
+  fn scaling_only_changes_the_length() {
+    let clamped = arrow([0.0, 0.0], [3.0, -4.0], Scaling::Clamped(2.0));
+    assert_eq!((clamped.magnitude, clamped.length), (5.0, 2.0));
+    let logarithmic = arrow([0.0, 0.0], [3.0, -4.0], Scaling::Logarithmic);
+    assert_eq!((logarithmic.magnitude, logarithmic.length), (5.0, 6.0f64.ln()));
+  }
+
+  #[test]
+  fn arrow_grid_points_away_from_a_positive_charge() {
+    let grid = Grid::new(200.0, 200.0, 3, 3).unwrap();
+    let charges = vec![charge(0, 1.0, [100.0, 100.0], "")];
+    let arrows = grid.sample(|point| arrow(point, crate::electric_field(&charges, point, &Config::default()), Scaling::Linear));
+    assert_eq!(arrows.len(), 9);
+    assert_eq!(arrows[5].position, [200.0, 100.0]);
+    assert_eq!(arrows[5].direction, [1.0, 0.0]);
+    assert_eq!(arrows[5].magnitude, 1.0);
+    assert_eq!(arrows[1].direction, [0.0, -1.0]);
+    // no field at the charge's own center
+    assert_eq!(arrows[4].magnitude, 0.0);
+  }
+}
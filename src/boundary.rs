@@ -0,0 +1,111 @@
+use crate::{Charge, Vector2};
+
+// what happens to charges that reach the edge of the canvas while the dynamics run
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
+pub enum Boundary {
+  // charges keep going and the field is that of the charges alone
+  #[default]
+  Open,
+  // charges bounce off walls `Charge.r` inside the canvas, keeping `restitution` of their normal speed
+  Reflecting { restitution: f64 },
+  // charges leaving one edge come back through the opposite one, and the field includes the
+  // images of every charge in the eight neighbouring copies of the canvas
+  Periodic,
+  // charges whose center leaves the canvas are removed from the scene
+  Absorbing,
+}
+
+// the charges that produce the field on a `width` x `height` canvas, with their periodic images
+pub fn images(charges: &[Charge], boundary: Boundary, width: f64, height: f64) -> Vec<Charge> {
+  match boundary {
+    Boundary::Periodic => {
+      let mut offsets: Vec<Vector2> = vec![];
+      for &dx in [-width, 0.0, width].iter() {
+        for &dy in [-height, 0.0, height].iter() {
+          offsets.push([dx, dy]);
+        }
+      }
+      charges.iter().flat_map(|charge|
+        offsets.iter().map(move |&offset| {
+          let mut image = charge.clone();
+          image.translate(offset);
+          image.image_offset = offset;
+          image
+        })
+      ).collect()
+    }
+    _ =>
+      charges.to_vec(),
+  }
+}
+
+// apply the boundary to charges that have just moved, fixed charges stay wherever they were pinned
+pub fn confine(charges: &mut Vec<Charge>, boundary: Boundary, width: f64, height: f64) {
+  match boundary {
+    Boundary::Open => {}
+    Boundary::Reflecting { restitution } =>
+      for charge in charges.iter_mut().filter(|charge| !charge.constraint.is_fixed()) {
+        let [x, y] = charge.center();
+        let (new_x, velocity_x) = reflect(x, charge.velocity.x, charge.r, width - charge.r, restitution);
+        let (new_y, velocity_y) = reflect(y, charge.velocity.y, charge.r, height - charge.r, restitution);
+        charge.translate([new_x - x, new_y - y]);
+        charge.velocity.x = velocity_x;
+        charge.velocity.y = velocity_y;
+      },
+    Boundary::Periodic =>
+      for charge in charges.iter_mut().filter(|charge| !charge.constraint.is_fixed()) {
+        let [x, y] = charge.center();
+        charge.translate([x.rem_euclid(width) - x, y.rem_euclid(height) - y]);
+      },
+    Boundary::Absorbing =>
+      charges.retain(|charge| {
+        let [x, y] = charge.center();
+        charge.constraint.is_fixed() || ((0.0..=width).contains(&x) && (0.0..=height).contains(&y))
+      }),
+  }
+}
+
+// mirror a coordinate that went past `low` or `high` back inside and turn its velocity around
+fn reflect(position: f64, velocity: f64, low: f64, high: f64, restitution: f64) -> (f64, f64) {
+  if position < low {
+    ((2.0 * low - position).min(high).max(low), restitution * velocity.abs())
+  } else if position > high {
+    ((2.0 * high - position).max(low).min(high), -restitution * velocity.abs())
+  } else {
+    (position, velocity)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::charge;
+
+  #[test]
+  fn periodic_charges_wrap_around() {
+    let mut charges = vec![charge(0, 1.0, [610.0, -5.0], "")];
+    confine(&mut charges, Boundary::Periodic, 600.0, 400.0);
+    let [x, y] = charges[0].center();
+    assert!((x - 10.0).abs() < 1e-9 && (y - 395.0).abs() < 1e-9);
+    assert_eq!(images(&charges, Boundary::Periodic, 600.0, 400.0).len(), 9);
+  }
+
+  #[test]
+  fn reflecting_walls_keep_restitution_of_the_speed() {
+    let mut charges = vec![charge(0, 1.0, [595.0, 200.0], r#","velocity":{"x":40.0,"y":10.0}"#)];
+    confine(&mut charges, Boundary::Reflecting { restitution: 0.5 }, 600.0, 400.0);
+    assert_eq!(charges[0].center(), [585.0, 200.0]);
+    assert_eq!([charges[0].velocity.x, charges[0].velocity.y], [-20.0, 10.0]);
+  }
+
+  #[test]
+  fn absorbing_walls_remove_charges_that_leave() {
+    let mut charges = vec![
+      charge(0, 1.0, [-1.0, 200.0], ""),
+      charge(1, 1.0, [300.0, 200.0], ""),
+      charge(2, 1.0, [-1.0, 200.0], r#","constraint":"Fixed""#),
+    ];
+    confine(&mut charges, Boundary::Absorbing, 600.0, 400.0);
+    assert_eq!(charges.iter().map(|charge| charge.id).collect::<Vec<usize>>(), vec![1, 2]);
+  }
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::grid::{self, Grid};
+use crate::{Line, Point};
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Levels {
+  // explicit potential values
+  Values(Vec<f64>),
+  // this many levels evenly spaced over the bulk of the sampled potentials
+  Count(usize),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Contour {
+  pub level: f64,
+  pub lines: Vec<Line>,
+}
+
+// a grid edge: from node (column, row) to the right or downwards
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Edge {
+  Horizontal(usize, usize),
+  Vertical(usize, usize),
+}
+
+pub fn contour_levels(levels: &Levels, values: &[f64]) -> Vec<f64> {
+  match levels {
+    Levels::Values(values) =>
+      values.clone(),
+    Levels::Count(count) =>
+      match grid::trimmed_range(values) {
+        Some((low, high)) => {
+          let spacing = (high - low) / ((count + 1) as f64);
+          (1..=*count).map(|index| low + spacing * (index as f64)).collect()
+        }
+        None =>
+          vec![],
+      }
+  }
+}
+
+// marching squares over `values` sampled on `grid`, joined into polylines
+pub fn contour(grid: &Grid, values: &[f64], level: f64) -> Vec<Line> {
+  let value = |column: usize, row: usize| values[row * grid.columns + column];
+  let mut crossings: HashMap<Edge, Point> = HashMap::new();
+  let mut crossing = |edge: Edge| {
+    *crossings.entry(edge).or_insert_with(|| {
+      let (start, end) =
+        match edge {
+          Edge::Horizontal(column, row) => ((column, row), (column + 1, row)),
+          Edge::Vertical(column, row) => ((column, row), (column, row + 1)),
+        };
+      let start_value = value(start.0, start.1);
+      let end_value = value(end.0, end.1);
+      let t = (level - start_value) / (end_value - start_value);
+      vecmath::vec2_add(
+        grid.point(start.0, start.1),
+        vecmath::vec2_scale(vecmath::vec2_sub(grid.point(end.0, end.1), grid.point(start.0, start.1)), t)
+      )
+    })
+  };
+  let mut segments: Vec<(Edge, Edge)> = vec![];
+  for row in 0..grid.rows - 1 {
+    for column in 0..grid.columns - 1 {
+      let corners =
+        [ value(column, row), value(column + 1, row)
+        , value(column + 1, row + 1), value(column, row + 1)
+        ];
+      if corners.iter().any(|corner| !corner.is_finite()) {
+        continue;
+      }
+      let above = corners.map(|corner| corner >= level);
+      let top = Edge::Horizontal(column, row);
+      let right = Edge::Vertical(column + 1, row);
+      let bottom = Edge::Horizontal(column, row + 1);
+      let left = Edge::Vertical(column, row);
+      let crossed =
+        [ (above[0] != above[1], top)
+        , (above[1] != above[2], right)
+        , (above[2] != above[3], bottom)
+        , (above[3] != above[0], left)
+        ].iter().filter(|(crossed, _)| *crossed).map(|(_, edge)| *edge).collect::<Vec<Edge>>();
+      match crossed.len() {
+        2 =>
+          segments.push((crossed[0], crossed[1])),
+        4 => {
+          // saddle: the average of the corners decides which diagonal stays connected
+          let center_above = corners.iter().sum::<f64>() / 4.0 >= level;
+          if above[0] == center_above {
+            segments.push((top, right));
+            segments.push((bottom, left));
+          } else {
+            segments.push((left, top));
+            segments.push((right, bottom));
+          }
+        }
+        _ =>
+          (),
+      }
+    }
+  }
+  join_segments(&segments).into_iter().map(|edges|
+    edges.into_iter().map(&mut crossing).collect()
+  ).collect()
+}
+
+// chain segments that share an edge into polylines of edges
+fn join_segments(segments: &[(Edge, Edge)]) -> Vec<VecDeque<Edge>> {
+  let mut touching: HashMap<Edge, Vec<usize>> = HashMap::new();
+  for (index, (a, b)) in segments.iter().enumerate() {
+    touching.entry(*a).or_default().push(index);
+    touching.entry(*b).or_default().push(index);
+  }
+  let mut used = vec![false; segments.len()];
+  let mut polylines = vec![];
+  for first in 0..segments.len() {
+    if used[first] {
+      continue;
+    }
+    used[first] = true;
+    let mut polyline = VecDeque::from(vec![segments[first].0, segments[first].1]);
+    for forwards in [true, false] {
+      loop {
+        let end = if forwards { *polyline.back().unwrap() } else { *polyline.front().unwrap() };
+        let next =
+          touching[&end].iter().cloned().find(|index| !used[*index]);
+        match next {
+          Some(index) => {
+            used[index] = true;
+            let (a, b) = segments[index];
+            let other = if a == end { b } else { a };
+            if forwards { polyline.push_back(other) } else { polyline.push_front(other) }
+          }
+          None =>
+            break,
+        }
+      }
+    }
+    polylines.push(polyline);
+  }
+  polylines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::charge;
+  use crate::Config;
+
+  #[test]
+  fn equipotentials_of_a_point_charge_are_closed_circles() {
+    let grid = Grid::new(400.0, 400.0, 81, 81).unwrap();
+    let source = charge(0, 1.0, [200.0, 200.0], "");
+    let potentials = grid.sample(|point| source.potential_at(point, &Config::default()));
+    // 1 / r with r = 1 m = 100 px
+    let lines = contour(&grid, &potentials, 1.0);
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line.first(), line.last());
+    for point in line {
+      let radius = crate::distance(*point, [200.0, 200.0]);
+      assert!((radius - 100.0).abs() < 2.0, "point {:?} is {} px away", point, radius);
+    }
+  }
+
+  #[test]
+  fn levels_are_spread_inside_the_range() {
+    let values = (0..100).map(|value| value as f64).collect::<Vec<f64>>();
+    let levels = contour_levels(&Levels::Count(3), &values);
+    assert_eq!(levels.len(), 3);
+    assert!(levels.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(levels[0] > 5.0 && levels[2] < 94.0);
+  }
+}
@@ -0,0 +1,461 @@
+use crate::boundary;
+use crate::{distance, electric_field, external_potential, Charge, Config, Position, Sign, Vector2};
+
+// restriction on how a single charge may move, forces along the restriction are cancelled
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum Constraint {
+  #[default]
+  Free,
+  // pinned in place, other charges bounce off it as if it were infinitely heavy
+  Fixed,
+  // slides along the line through `point` in `direction`, like a bead on a wire
+  Line { point: Position, direction: Position },
+  // stays `radius` away from `center`
+  Circle { center: Position, radius: f64 },
+}
+
+impl Constraint {
+  pub fn is_fixed(&self) -> bool {
+    matches!(self, Constraint::Fixed)
+  }
+}
+
+// what happens when the outlines of two charges, circles of radius `Charge.r`, touch
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
+pub enum Collision {
+  #[default]
+  PassThrough,
+  Elastic,
+  // the charges bounce apart with `restitution` of their approach speed
+  Inelastic { restitution: f64 },
+  // the lighter charge joins the heavier one, keeping mass, momentum and total charge,
+  // opposite charges of equal magnitude annihilate and dipoles bounce elastically
+  Merge,
+}
+
+// relative difference below which opposite charges count as equal and annihilate when merging
+const CANCEL_TOLERANCE: f64 = 1e-9;
+
+// totals over all charges after a step, in SI units, to spot drift in the integration
+#[derive(Serialize, Debug, Clone)]
+pub struct Diagnostics {
+  kinetic_energy: f64,
+  potential_energy: f64,
+  total_energy: f64,
+  momentum: Vector2,
+}
+
+// advance every charge by `dt` seconds with velocity Verlet, which keeps the energy of orbits from drifting.
+// Each source is pushed as if its net charge sat at its center, so dipoles feel no force
+pub fn step(charges: &mut Vec<Charge>, dt: f64, config: &Config, width: f64, height: f64) {
+  let old_accelerations = accelerations(charges, config, width, height);
+  for (charge, acceleration) in charges.iter_mut().zip(&old_accelerations) {
+    // a pinned charge stays put whatever velocity it was given
+    if charge.constraint.is_fixed() {
+      continue;
+    }
+    let velocity = [charge.velocity.x, charge.velocity.y];
+    charge.translate(
+      vecmath::vec2_add(vecmath::vec2_scale(velocity, dt), vecmath::vec2_scale(*acceleration, dt * dt / 2.0))
+    );
+    constrain(charge);
+  }
+  let new_accelerations = accelerations(charges, config, width, height);
+  for ((charge, acceleration), new_acceleration) in charges.iter_mut().zip(&old_accelerations).zip(&new_accelerations) {
+    let [dvx, dvy] = vecmath::vec2_scale(vecmath::vec2_add(*acceleration, *new_acceleration), dt / 2.0);
+    charge.velocity.x += dvx;
+    charge.velocity.y += dvy;
+  }
+  collide(charges, config.collision);
+  boundary::confine(charges, config.boundary, width, height);
+  for charge in charges.iter_mut() {
+    constrain(charge);
+  }
+}
+
+// project a charge that drifted off its constraint back onto it, keeping only the velocity along it
+fn constrain(charge: &mut Charge) {
+  let center = charge.center();
+  let velocity = [charge.velocity.x, charge.velocity.y];
+  let (position, velocity) =
+    match &charge.constraint {
+      Constraint::Free =>
+        return,
+      Constraint::Fixed =>
+        (center, [0.0, 0.0]),
+      Constraint::Line { point, direction } => {
+        let point = [point.x, point.y];
+        let tangent = vecmath::vec2_normalized([direction.x, direction.y]);
+        let along = vecmath::vec2_dot(vecmath::vec2_sub(center, point), tangent);
+        (vecmath::vec2_add(point, vecmath::vec2_scale(tangent, along)), project(velocity, tangent))
+      }
+      Constraint::Circle { center: circle_center, radius } => {
+        let circle_center = [circle_center.x, circle_center.y];
+        let offset = vecmath::vec2_sub(center, circle_center);
+        if vecmath::vec2_len(offset) == 0.0 {
+          // every direction is as close, leave the charge for the next step to move it off the center
+          return;
+        }
+        let radial = vecmath::vec2_normalized(offset);
+        let tangent = [-radial[1], radial[0]];
+        (vecmath::vec2_add(circle_center, vecmath::vec2_scale(radial, *radius)), project(velocity, tangent))
+      }
+    };
+  charge.translate(vecmath::vec2_sub(position, center));
+  charge.velocity.x = velocity[0];
+  charge.velocity.y = velocity[1];
+}
+
+// the part of an acceleration a constrained charge can follow, the rest is taken up by the constraint
+fn constrained_acceleration(charge: &Charge, acceleration: Vector2) -> Vector2 {
+  match &charge.constraint {
+    Constraint::Free =>
+      acceleration,
+    Constraint::Fixed =>
+      [0.0, 0.0],
+    Constraint::Line { direction, .. } =>
+      project(acceleration, vecmath::vec2_normalized([direction.x, direction.y])),
+    Constraint::Circle { center, .. } => {
+      let offset = vecmath::vec2_sub(charge.center(), [center.x, center.y]);
+      if vecmath::vec2_len(offset) == 0.0 {
+        return acceleration;
+      }
+      let radial = vecmath::vec2_normalized(offset);
+      project(acceleration, [-radial[1], radial[0]])
+    }
+  }
+}
+
+// component of `vector` along the unit vector `axis`
+fn project(vector: Vector2, axis: Vector2) -> Vector2 {
+  vecmath::vec2_scale(axis, vecmath::vec2_dot(vector, axis))
+}
+
+// a fixed charge takes any impulse without moving
+fn inverse_mass(charge: &Charge) -> f64 {
+  match charge.constraint {
+    Constraint::Fixed => 0.0,
+    _ => 1.0 / charge.mass,
+  }
+}
+
+fn collide(charges: &mut Vec<Charge>, collision: Collision) {
+  let mut index = 0;
+  'charges: while index < charges.len() {
+    let mut other_index = index + 1;
+    while other_index < charges.len() {
+      if !touching(&charges[index], &charges[other_index]) {
+        other_index += 1;
+        continue;
+      }
+      let restitution =
+        match collision {
+          Collision::PassThrough =>
+            return,
+          Collision::Elastic =>
+            1.0,
+          Collision::Inelastic { restitution } =>
+            restitution,
+          // a dipole's moment can't be added to a point charge's, so pairs with a dipole bounce instead
+          Collision::Merge if charges[index].moment_angle().is_some() || charges[other_index].moment_angle().is_some() =>
+            1.0,
+          Collision::Merge => {
+            match merge(&charges[index], &charges[other_index]) {
+              Some(merged) => {
+                charges[index] = merged;
+                charges.remove(other_index);
+                // the merged charge is larger and may now touch charges checked before
+                other_index = index + 1;
+              }
+              None => {
+                charges.remove(other_index);
+                charges.remove(index);
+                continue 'charges;
+              }
+            }
+            continue;
+          }
+        };
+      let (head, tail) = charges.split_at_mut(other_index);
+      bounce(&mut head[index], &mut tail[0], restitution);
+      other_index += 1;
+    }
+    index += 1;
+  }
+}
+
+fn touching(a: &Charge, b: &Charge) -> bool {
+  distance(a.center(), b.center()) < a.r + b.r
+}
+
+// exchange an impulse along the line between the centers, unless the charges already move apart
+fn bounce(a: &mut Charge, b: &mut Charge, restitution: f64) {
+  let offset = vecmath::vec2_sub(b.center(), a.center());
+  let length = vecmath::vec2_len(offset);
+  if length == 0.0 {
+    return;
+  }
+  let normal = vecmath::vec2_scale(offset, 1.0 / length);
+  let relative_velocity = [a.velocity.x - b.velocity.x, a.velocity.y - b.velocity.y];
+  let approach_speed = vecmath::vec2_dot(relative_velocity, normal);
+  if approach_speed <= 0.0 {
+    return;
+  }
+  let (a_inverse_mass, b_inverse_mass) = (inverse_mass(a), inverse_mass(b));
+  if a_inverse_mass + b_inverse_mass == 0.0 {
+    return;
+  }
+  let impulse = (1.0 + restitution) * approach_speed / (a_inverse_mass + b_inverse_mass);
+  a.velocity.x -= impulse * a_inverse_mass * normal[0];
+  a.velocity.y -= impulse * a_inverse_mass * normal[1];
+  b.velocity.x += impulse * b_inverse_mass * normal[0];
+  b.velocity.y += impulse * b_inverse_mass * normal[1];
+}
+
+// the heavier charge moved to the common center of mass and carrying both charges,
+// or None when two opposite charges cancel out. The outline grows to cover the area of both
+fn merge(a: &Charge, b: &Charge) -> Option<Charge> {
+  // a fixed charge stays where it is and keeps its constraint whatever joins it
+  let (kept, joining) =
+    match (&a.constraint, &b.constraint) {
+      (Constraint::Fixed, _) => (a, b),
+      (_, Constraint::Fixed) => (b, a),
+      _ if b.mass > a.mass => (b, a),
+      _ => (a, b),
+    };
+  let mass = a.mass + b.mass;
+  let (kept_charge, joining_charge) = (kept.net_charge(), joining.net_charge());
+  let opposite = kept_charge * joining_charge < 0.0;
+  let cancelling = (kept_charge + joining_charge).abs() <= CANCEL_TOLERANCE * kept_charge.abs().max(joining_charge.abs());
+  if opposite && cancelling {
+    return None;
+  }
+  // whatever rounding leaves of charges that nearly cancel is dropped, neutral charges keep their mass
+  let net_charge = if cancelling { 0.0 } else { kept_charge + joining_charge };
+  let mut merged = kept.clone();
+  let center =
+    vecmath::vec2_scale(
+      vecmath::vec2_add(vecmath::vec2_scale(a.center(), a.mass), vecmath::vec2_scale(b.center(), b.mass)),
+      1.0 / mass
+    );
+  if !kept.constraint.is_fixed() {
+    merged.translate(vecmath::vec2_sub(center, kept.center()));
+  }
+  merged.velocity.x = (a.velocity.x * a.mass + b.velocity.x * b.mass) / mass;
+  merged.velocity.y = (a.velocity.y * a.mass + b.velocity.y * b.mass) / mass;
+  merged.mass = mass;
+  merged.r = (a.r.powf(2.0) + b.r.powf(2.0)).sqrt();
+  merged.sign = if net_charge < 0.0 { Sign::Negative } else { Sign::Positive };
+  merged.magnitude = net_charge.abs();
+  constrain(&mut merged);
+  Some(merged)
+}
+
+// acceleration of each charge in pixels per second squared, from the field of all the others
+fn accelerations(charges: &[Charge], config: &Config, width: f64, height: f64) -> Vec<Vector2> {
+  let sources = boundary::images(charges, config.boundary, width, height);
+  charges.iter().map(|charge| {
+    let other_charges =
+      sources.iter()
+        .filter(|other| other.id != charge.id)
+        .cloned()
+        .collect::<Vec<Charge>>();
+    let force = vecmath::vec2_scale(electric_field(&other_charges, charge.center(), config), charge.net_charge());
+    constrained_acceleration(charge, vecmath::vec2_scale(force, config.pixels_per_meter / charge.mass))
+  }).collect()
+}
+
+pub fn diagnostics(charges: &[Charge], config: &Config, width: f64, height: f64) -> Diagnostics {
+  let sources = boundary::images(charges, config.boundary, width, height);
+  let kinetic_energy =
+    charges.iter().map(|charge|
+      charge.mass * vecmath::vec2_square_len(velocity_in_meters(charge, config)) / 2.0
+    ).sum::<f64>();
+  // every pair is counted from both ends, hence the half, the external field acts on each charge once
+  let potential_energy =
+    charges.iter().map(|charge| {
+      let point = charge.center();
+      let pair_potential =
+        sources.iter()
+          .filter(|other| other.id != charge.id)
+          .map(|other| other.potential_at(point, config))
+          .sum::<f64>();
+      charge.net_charge() * (pair_potential / 2.0 + external_potential(point, config))
+    }).sum::<f64>();
+  let momentum =
+    charges.iter().fold([0.0, 0.0], |sum, charge|
+      vecmath::vec2_add(sum, vecmath::vec2_scale(velocity_in_meters(charge, config), charge.mass))
+    );
+  Diagnostics {
+    kinetic_energy,
+    potential_energy,
+    total_energy: kinetic_energy + potential_energy,
+    momentum,
+  }
+}
+
+fn velocity_in_meters(charge: &Charge, config: &Config) -> Vector2 {
+  vecmath::vec2_scale([charge.velocity.x, charge.velocity.y], 1.0 / config.pixels_per_meter)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::charge;
+
+  const WIDTH: f64 = 1000.0;
+  const HEIGHT: f64 = 1000.0;
+
+  fn total_charge(charges: &[Charge]) -> f64 {
+    charges.iter().map(|charge| charge.net_charge()).sum()
+  }
+
+  #[test]
+  fn verlet_orbit_keeps_its_energy() {
+    // a light charge circling a heavy one, 1 m apart with v^2 = kQq / (m r)
+    let mut charges = vec![
+      charge(0, 1.0, [500.0, 500.0], r#","mass":1e9"#),
+      charge(1, -1.0, [600.0, 500.0], r#","velocity":{"x":0.0,"y":100.0}"#),
+    ];
+    let config = Config::default();
+    let start = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    for _ in 0..2000 {
+      step(&mut charges, 0.01, &config, WIDTH, HEIGHT);
+    }
+    let end = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    let drift = ((end.total_energy - start.total_energy) / start.total_energy).abs();
+    assert!(drift < 1e-4, "energy drifted by {}", drift);
+    let radius = distance(charges[0].center(), charges[1].center());
+    assert!((radius - 100.0).abs() < 0.1, "orbit radius {}", radius);
+  }
+
+  #[test]
+  fn diagnostics_in_si_units() {
+    // 2 m apart, moving at 3 m/s and 1 m/s in opposite directions
+    let charges = vec![
+      charge(0, 2.0, [100.0, 100.0], r#","velocity":{"x":300.0,"y":0.0}"#),
+      charge(1, -3.0, [300.0, 100.0], r#","mass":2.0,"velocity":{"x":0.0,"y":-100.0}"#),
+    ];
+    let config = Config { external_field: Position { x: 0.0, y: 1.0 }, ..Config::default() };
+    let diagnostics = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    assert_eq!(diagnostics.kinetic_energy, 4.5 + 1.0);
+    // k q1 q2 / r, plus -q E·r for each charge in the external field
+    assert!((diagnostics.potential_energy - (-3.0 + -2.0 * 1.0 + 3.0 * 1.0)).abs() < 1e-12);
+    assert_eq!(diagnostics.total_energy, diagnostics.kinetic_energy + diagnostics.potential_energy);
+    assert_eq!(diagnostics.momentum, [3.0, -2.0]);
+  }
+
+  #[test]
+  fn merging_keeps_charge_and_momentum() {
+    let mut charges = vec![
+      charge(0, 2.0, [500.0, 500.0], r#","velocity":{"x":100.0,"y":0.0}"#),
+      charge(1, -1.0, [505.0, 500.0], r#","mass":3.0,"velocity":{"x":0.0,"y":-20.0}"#),
+    ];
+    let config = Config { collision: Collision::Merge, ..Config::default() };
+    let momentum = diagnostics(&charges, &config, WIDTH, HEIGHT).momentum;
+    collide(&mut charges, config.collision);
+    assert_eq!(charges.len(), 1);
+    assert_eq!(charges[0].id, 1);
+    assert_eq!(charges[0].mass, 4.0);
+    assert!((total_charge(&charges) - 1.0).abs() < 1e-12);
+    let merged_momentum = diagnostics(&charges, &config, WIDTH, HEIGHT).momentum;
+    assert!(distance(merged_momentum, momentum) < 1e-12);
+  }
+
+  #[test]
+  fn opposite_charges_annihilate() {
+    let mut charges = vec![charge(0, 1.0, [500.0, 500.0], ""), charge(1, -1.0, [505.0, 500.0], "")];
+    collide(&mut charges, Collision::Merge);
+    assert!(charges.is_empty());
+  }
+
+  #[test]
+  fn dipoles_bounce_instead_of_merging() {
+    let mut charges = vec![
+      charge(0, 3.0, [500.0, 500.0], r#","velocity":{"x":10.0,"y":0.0}"#),
+      charge(1, 1.0, [505.0, 500.0], r#","mass":5.0,"shape":{"Dipole":{"moment":{"x":1.0,"y":0.0}}}"#),
+    ];
+    collide(&mut charges, Collision::Merge);
+    assert_eq!(charges.len(), 2);
+    assert_eq!(total_charge(&charges), 3.0);
+    assert!(charges[0].velocity.x < 0.0);
+  }
+
+  #[test]
+  fn elastic_collisions_keep_energy_and_momentum() {
+    let mut charges = vec![
+      charge(0, 0.0, [500.0, 500.0], r#","velocity":{"x":100.0,"y":30.0}"#),
+      charge(1, 0.0, [515.0, 505.0], r#","mass":2.0,"velocity":{"x":-20.0,"y":0.0}"#),
+    ];
+    let config = Config::default();
+    let before = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    collide(&mut charges, Collision::Elastic);
+    let after = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    assert!((after.kinetic_energy - before.kinetic_energy).abs() < 1e-12);
+    assert!(distance(after.momentum, before.momentum) < 1e-12);
+    assert!(charges[0].velocity.x < 100.0);
+  }
+
+  #[test]
+  fn inelastic_collisions_lose_energy_but_keep_momentum() {
+    let mut charges = vec![
+      charge(0, 0.0, [500.0, 500.0], r#","velocity":{"x":100.0,"y":0.0}"#),
+      charge(1, 0.0, [515.0, 500.0], ""),
+    ];
+    let config = Config::default();
+    let before = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    collide(&mut charges, Collision::Inelastic { restitution: 0.0 });
+    let after = diagnostics(&charges, &config, WIDTH, HEIGHT);
+    assert!(distance(after.momentum, before.momentum) < 1e-12);
+    // with no restitution both move on together
+    assert!((charges[0].velocity.x - 50.0).abs() < 1e-9 && (charges[1].velocity.x - 50.0).abs() < 1e-9);
+    assert!((after.kinetic_energy - before.kinetic_energy / 2.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn fixed_charges_stay_put() {
+    let mut charges = vec![
+      charge(0, 1.0, [500.0, 500.0], r#","velocity":{"x":50.0,"y":0.0},"constraint":"Fixed""#),
+      charge(1, 1.0, [600.0, 500.0], ""),
+    ];
+    let config = Config::default();
+    step(&mut charges, 0.1, &config, WIDTH, HEIGHT);
+    assert_eq!(charges[0].center(), [500.0, 500.0]);
+    assert_eq!([charges[0].velocity.x, charges[0].velocity.y], [0.0, 0.0]);
+    assert!(charges[1].center()[0] > 600.0);
+  }
+
+  #[test]
+  fn constrained_charges_stay_on_their_curve() {
+    let mut charges = vec![
+      charge(0, 1.0, [500.0, 500.0], r#","constraint":"Fixed""#),
+      charge(1, -1.0, [600.0, 500.0], r#","velocity":{"x":0.0,"y":50.0},"constraint":{"Circle":{"center":{"x":500.0,"y":500.0},"radius":100.0}}"#),
+      charge(2, -0.1, [100.0, 100.0], r#","constraint":{"Line":{"point":{"x":0.0,"y":100.0},"direction":{"x":1.0,"y":0.0}}}"#),
+    ];
+    let config = Config::default();
+    for _ in 0..500 {
+      step(&mut charges, 0.01, &config, WIDTH, HEIGHT);
+    }
+    assert!((distance(charges[1].center(), [500.0, 500.0]) - 100.0).abs() < 1e-9);
+    assert!((charges[2].center()[1] - 100.0).abs() < 1e-9);
+    assert!(charges[2].center()[0] > 100.0);
+  }
+
+  #[test]
+  fn neutral_charges_merge_instead_of_vanishing() {
+    let mut charges = vec![
+      charge(0, 0.0, [500.0, 500.0], r#","mass":5.0,"velocity":{"x":10.0,"y":0.0}"#),
+      charge(1, 0.0, [505.0, 500.0], r#","mass":5.0"#),
+    ];
+    collide(&mut charges, Collision::Merge);
+    assert_eq!(charges.len(), 1);
+    assert_eq!((charges[0].mass, charges[0].magnitude, charges[0].velocity.x), (10.0, 0.0, 5.0));
+  }
+
+  #[test]
+  fn nearly_equal_opposite_charges_annihilate() {
+    let mut charges = vec![charge(0, 0.3, [500.0, 500.0], ""), charge(1, -(0.1 + 0.2), [505.0, 500.0], "")];
+    collide(&mut charges, Collision::Merge);
+    assert!(charges.is_empty());
+  }
+}
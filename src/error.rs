@@ -0,0 +1,453 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+use crate::boundary::Boundary;
+use crate::contour::Levels;
+use crate::dynamics::{Collision, Constraint};
+use crate::probe::PathSampling;
+use crate::sources::Shape;
+use crate::{BoundarySeeding, Charge, Config, Field, Point, Position, Tracing};
+
+// fields beyond this many seed lines are almost certainly a typo and would freeze the page
+pub const MAX_DENSITY: usize = 10000;
+
+// same for the steps of a single line, each of which sums the field of every source
+const MAX_STEPS: usize = 100000;
+
+// same for the samples taken along a path
+const MAX_PATH_SAMPLES: usize = 100000;
+
+// and for the pixels of a heatmap, a 4096 x 4096 canvas
+const MAX_PIXELS: usize = 16777216;
+
+// and for the nodes of a potential or arrow grid, 1024 x 1024
+const MAX_GRID_NODES: usize = 1048576;
+
+// each equipotential level is a full pass over the grid
+const MAX_LEVELS: usize = 1000;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind")]
+pub enum Error {
+  Parse { path: String, message: String },
+  InvalidSteps { path: String, steps: usize },
+  InvalidDensity { path: String, density: usize },
+  NonFinite { path: String, value: f64 },
+  OutOfRange { path: String, value: f64, expected: String },
+  DuplicateId { path: String, id: usize },
+  InvalidResolution { path: String, value: usize },
+  TooFewPoints { path: String, count: usize, minimum: usize },
+  TooManySamples { path: String, count: f64, maximum: usize },
+  UnsupportedShape { path: String, shape: String },
+  TooManyLevels { path: String, count: usize, maximum: usize },
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Parse { path, message } =>
+        write!(f, "I can't read `{}`: {}", path, message),
+      Error::InvalidSteps { path, steps } =>
+        write!(f, "`{}` is {} but a line needs between 1 and {} steps", path, steps, MAX_STEPS),
+      Error::InvalidDensity { path, density } =>
+        write!(f, "`{}` is {} but it can be at most {}", path, density, MAX_DENSITY),
+      Error::NonFinite { path, value } =>
+        write!(f, "`{}` is {} but it should be a finite number", path, value),
+      Error::OutOfRange { path, value, expected } =>
+        write!(f, "`{}` is {} but it should be {}", path, value, expected),
+      Error::DuplicateId { path, id } =>
+        write!(f, "`{}` is {} which is already used by another source", path, id),
+      Error::InvalidResolution { path, value } =>
+        write!(f, "`{}` is {} but a grid needs at least 2 nodes along each side", path, value),
+      Error::TooFewPoints { path, count, minimum } =>
+        write!(f, "`{}` has {} points but it needs at least {}", path, count, minimum),
+      Error::TooManySamples { path, count, maximum } =>
+        write!(f, "`{}` asks for {} samples but at most {} can be taken", path, count, maximum),
+      Error::UnsupportedShape { path, shape } =>
+        write!(f, "`{}` is a {} but only point charges and dipoles can move", path, shape),
+      Error::TooManyLevels { path, count, maximum } =>
+        write!(f, "`{}` asks for {} levels but at most {} can be traced", path, count, maximum),
+    }
+  }
+}
+
+// what JS receives: the error's fields plus a readable message
+#[derive(Serialize)]
+struct Report<'a> {
+  #[serde(flatten)]
+  error: &'a Error,
+  message: String,
+}
+
+impl From<Error> for JsValue {
+  #[allow(deprecated)]
+  fn from(error: Error) -> JsValue {
+    let message = error.to_string();
+    JsValue::from_serde(&Report { error: &error, message: message.clone() })
+      .unwrap_or_else(|_| JsValue::from_str(&message))
+  }
+}
+
+pub fn validate_size(width: f64, height: f64) -> Result<(), Error> {
+  check_positive("width", width)?;
+  check_positive("height", height)
+}
+
+pub fn validate_pixels(width: f64, height: f64) -> Result<(), Error> {
+  let count = width.round() * height.round();
+  if count > MAX_PIXELS as f64 {
+    return Err(Error::TooManySamples { path: "width * height".to_string(), count, maximum: MAX_PIXELS });
+  }
+  Ok(())
+}
+
+fn validate_shape(source: &Charge, path: &dyn Fn(&str) -> String) -> Result<(), Error> {
+  match &source.shape {
+    Shape::Point =>
+      Ok(()),
+    Shape::Segment { end } => {
+      check_finite(&path("Segment.end.x"), end.x)?;
+      check_finite(&path("Segment.end.y"), end.y)?;
+      check_positive(&path("Segment.length"), crate::distance(source.center(), [end.x, end.y]))
+    }
+    Shape::Ring { radius } =>
+      check_positive(&path("Ring.radius"), *radius),
+    Shape::Arc { radius, start_angle, end_angle } => {
+      check_positive(&path("Arc.radius"), *radius)?;
+      check_finite(&path("Arc.start_angle"), *start_angle)?;
+      check_finite(&path("Arc.end_angle"), *end_angle)?;
+      check_positive(&path("Arc.sweep"), (end_angle - start_angle).abs())
+    }
+    Shape::Disk { radius } =>
+      check_positive(&path("Disk.radius"), *radius),
+    Shape::Dipole { moment } => {
+      check_finite(&path("Dipole.moment.x"), moment.x)?;
+      check_finite(&path("Dipole.moment.y"), moment.y)?;
+      check_positive(&path("Dipole.moment"), (moment.x.powf(2.0) + moment.y.powf(2.0)).sqrt())
+    }
+    Shape::Polygon { vertices } => {
+      if vertices.len() < 3 {
+        return Err(Error::TooFewPoints { path: path("Polygon.vertices"), count: vertices.len(), minimum: 3 });
+      }
+      validate_points(vertices, &path("Polygon.vertices"))?;
+      let double_area =
+        (0..vertices.len()).map(|index| {
+          let (a, b) = (&vertices[index], &vertices[(index + 1) % vertices.len()]);
+          a.x * b.y - b.x * a.y
+        }).sum::<f64>();
+      check_positive(&path("Polygon.area"), double_area.abs() / 2.0)
+    }
+  }
+}
+
+fn validate_constraint(constraint: &Constraint, path: &dyn Fn(&str) -> String) -> Result<(), Error> {
+  match constraint {
+    Constraint::Free | Constraint::Fixed =>
+      Ok(()),
+    Constraint::Line { point, direction } => {
+      check_finite(&path("Line.point.x"), point.x)?;
+      check_finite(&path("Line.point.y"), point.y)?;
+      check_finite(&path("Line.direction.x"), direction.x)?;
+      check_finite(&path("Line.direction.y"), direction.y)?;
+      check_positive(&path("Line.direction"), (direction.x.powf(2.0) + direction.y.powf(2.0)).sqrt())
+    }
+    Constraint::Circle { center, radius } => {
+      check_finite(&path("Circle.center.x"), center.x)?;
+      check_finite(&path("Circle.center.y"), center.y)?;
+      check_positive(&path("Circle.radius"), *radius)
+    }
+  }
+}
+
+pub fn validate_resolution(columns: usize, rows: usize) -> Result<(), Error> {
+  if columns < 2 {
+    return Err(Error::InvalidResolution { path: "columns".to_string(), value: columns });
+  }
+  if rows < 2 {
+    return Err(Error::InvalidResolution { path: "rows".to_string(), value: rows });
+  }
+  let count = (columns as f64) * (rows as f64);
+  if count > MAX_GRID_NODES as f64 {
+    return Err(Error::TooManySamples { path: "columns * rows".to_string(), count, maximum: MAX_GRID_NODES });
+  }
+  Ok(())
+}
+
+pub fn validate_fields(fields: &[Field]) -> Result<(), Error> {
+  for (index, field) in fields.iter().enumerate() {
+    let path = |name: &str| format!("fields[{}].{}", index, name);
+    let source = &field.source;
+    if fields[..index].iter().any(|other| other.source.id == source.id) {
+      return Err(Error::DuplicateId { path: path("source.id"), id: source.id });
+    }
+    check_finite(&path("source.magnitude"), source.magnitude)?;
+    check_finite(&path("source.position.x"), source.position.x)?;
+    check_finite(&path("source.position.y"), source.position.y)?;
+    check_non_negative(&path("source.r"), source.r)?;
+    check_finite(&path("source.velocity.x"), source.velocity.x)?;
+    check_finite(&path("source.velocity.y"), source.velocity.y)?;
+    check_positive(&path("source.mass"), source.mass)?;
+    validate_constraint(&source.constraint, &|name| path(&format!("source.constraint.{}", name)))?;
+    validate_shape(source, &|name| path(&format!("source.shape.{}", name)))?;
+    if field.density > MAX_DENSITY {
+      return Err(Error::InvalidDensity { path: path("density"), density: field.density });
+    }
+    validate_tracing(&field.tracing, &path)?;
+    check_finite(&path("angle_offset"), field.angle_offset)?;
+  }
+  Ok(())
+}
+
+// the dynamics treat every source as sitting at its `position`, which only holds for points and dipoles
+pub fn validate_dynamics(fields: &[Field]) -> Result<(), Error> {
+  for (index, field) in fields.iter().enumerate() {
+    let shape =
+      match field.source.shape {
+        Shape::Point | Shape::Dipole { .. } => continue,
+        Shape::Segment { .. } => "Segment",
+        Shape::Ring { .. } => "Ring",
+        Shape::Arc { .. } => "Arc",
+        Shape::Disk { .. } => "Disk",
+        Shape::Polygon { .. } => "Polygon",
+      };
+    return Err(Error::UnsupportedShape { path: format!("fields[{}].source.shape", index), shape: shape.to_string() });
+  }
+  Ok(())
+}
+
+pub fn validate_seeding(seeding: &BoundarySeeding, width: f64, height: f64) -> Result<(), Error> {
+  check_positive("seeding.spacing", seeding.spacing)?;
+  // seeds along the four edges, as `boundary_seeds` places them
+  let count = 2.0 * ((width / seeding.spacing).floor() + (height / seeding.spacing).floor());
+  if count > MAX_DENSITY as f64 {
+    return Err(Error::TooManySamples { path: "seeding.spacing".to_string(), count, maximum: MAX_DENSITY });
+  }
+  validate_tracing(&seeding.tracing, &|name| format!("seeding.{}", name))
+}
+
+fn validate_tracing(tracing: &Tracing, path: &dyn Fn(&str) -> String) -> Result<(), Error> {
+  if tracing.steps == 0 || tracing.steps > MAX_STEPS {
+    return Err(Error::InvalidSteps { path: path("steps"), steps: tracing.steps });
+  }
+  check_positive(&path("delta"), tracing.delta)?;
+  check_positive(&path("tolerance"), tracing.tolerance)
+}
+
+pub fn validate_levels(levels: &Levels) -> Result<(), Error> {
+  let (path, count) =
+    match levels {
+      Levels::Values(values) => {
+        for (index, value) in values.iter().enumerate() {
+          check_finite(&format!("levels.Values[{}]", index), *value)?;
+        }
+        ("levels.Values", values.len())
+      }
+      Levels::Count(count) =>
+        ("levels.Count", *count),
+    };
+  if count > MAX_LEVELS {
+    return Err(Error::TooManyLevels { path: path.to_string(), count, maximum: MAX_LEVELS });
+  }
+  Ok(())
+}
+
+pub fn validate_points(points: &[Position], path: &str) -> Result<(), Error> {
+  for (index, point) in points.iter().enumerate() {
+    check_finite(&format!("{}[{}].x", path, index), point.x)?;
+    check_finite(&format!("{}[{}].y", path, index), point.y)?;
+  }
+  Ok(())
+}
+
+pub fn validate_path(path: &[Point], sampling: PathSampling) -> Result<(), Error> {
+  if path.len() < 2 {
+    return Err(Error::TooFewPoints { path: "path".to_string(), count: path.len(), minimum: 2 });
+  }
+  let (name, count) =
+    match sampling {
+      PathSampling::Count(count) =>
+        ("sampling.Count", count as f64),
+      PathSampling::Spacing(spacing) => {
+        check_positive("sampling.Spacing", spacing)?;
+        let length = path.windows(2).map(|segment| crate::distance(segment[0], segment[1])).sum::<f64>();
+        ("sampling.Spacing", (length / spacing).ceil() + 1.0)
+      }
+    };
+  if count > MAX_PATH_SAMPLES as f64 {
+    return Err(Error::TooManySamples { path: name.to_string(), count, maximum: MAX_PATH_SAMPLES });
+  }
+  Ok(())
+}
+
+pub fn validate_test_charge(test_charge: f64) -> Result<(), Error> {
+  check_finite("test_charge", test_charge)
+}
+
+pub fn validate_time_step(dt: f64) -> Result<(), Error> {
+  check_non_negative("dt", dt)
+}
+
+pub fn validate_config(config: &Config) -> Result<(), Error> {
+  check_positive("config.pixels_per_meter", config.pixels_per_meter)?;
+  check_finite("config.k", config.k)?;
+  check_non_negative("config.softening", config.softening)?;
+  check_non_negative("config.bounds_margin", config.bounds_margin)?;
+  if let Some(lines_per_unit_charge) = config.lines_per_unit_charge {
+    check_non_negative("config.lines_per_unit_charge", lines_per_unit_charge)?;
+  }
+  check_finite("config.external_field.x", config.external_field.x)?;
+  check_finite("config.external_field.y", config.external_field.y)?;
+  if let Boundary::Reflecting { restitution } = config.boundary {
+    check_fraction("config.boundary.Reflecting.restitution", restitution)?;
+  }
+  if let Collision::Inelastic { restitution } = config.collision {
+    check_fraction("config.collision.Inelastic.restitution", restitution)?;
+  }
+  Ok(())
+}
+
+fn check_finite(path: &str, value: f64) -> Result<(), Error> {
+  if value.is_finite() {
+    Ok(())
+  } else {
+    Err(Error::NonFinite { path: path.to_string(), value })
+  }
+}
+
+fn check_positive(path: &str, value: f64) -> Result<(), Error> {
+  check_finite(path, value)?;
+  if value > 0.0 {
+    Ok(())
+  } else {
+    Err(Error::OutOfRange { path: path.to_string(), value, expected: "greater than 0".to_string() })
+  }
+}
+
+fn check_non_negative(path: &str, value: f64) -> Result<(), Error> {
+  check_finite(path, value)?;
+  if value >= 0.0 {
+    Ok(())
+  } else {
+    Err(Error::OutOfRange { path: path.to_string(), value, expected: "at least 0".to_string() })
+  }
+}
+
+fn check_fraction(path: &str, value: f64) -> Result<(), Error> {
+  check_non_negative(path, value)?;
+  if value <= 1.0 {
+    Ok(())
+  } else {
+    Err(Error::OutOfRange { path: path.to_string(), value, expected: "at most 1".to_string() })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fields(json: &str) -> Vec<Field> {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn reports_carry_the_kind_path_and_message() {
+    let error = Error::InvalidDensity { path: "fields[1].density".to_string(), density: 20000 };
+    let report = serde_json::to_value(Report { error: &error, message: error.to_string() }).unwrap();
+    assert_eq!(report["kind"], "InvalidDensity");
+    assert_eq!(report["path"], "fields[1].density");
+    assert_eq!(report["density"], 20000);
+    assert_eq!(report["message"], "`fields[1].density` is 20000 but it can be at most 10000");
+  }
+
+  #[test]
+  fn invalid_fields_are_reported_with_their_index() {
+    let source = r#""source":{"id":0,"sign":"Positive","magnitude":1.0,"position":{"x":0.0,"y":0.0},"r":10.0}"#;
+    let valid = format!(r#"{{{},"density":4,"steps":10,"delta":1.0}}"#, source);
+    assert!(validate_fields(&fields(&format!("[{}]", valid))).is_ok());
+    let zero_steps = format!(r#"{{{},"density":4,"steps":0,"delta":1.0}}"#, source.replace(r#""id":0"#, r#""id":1"#));
+    match validate_fields(&fields(&format!("[{},{}]", valid, zero_steps))) {
+      Err(Error::InvalidSteps { path, steps }) => assert_eq!((path.as_str(), steps), ("fields[1].steps", 0)),
+      other => panic!("expected invalid steps, got {:?}", other),
+    }
+    match validate_fields(&fields(&format!("[{},{}]", valid, valid))) {
+      Err(Error::DuplicateId { path, id }) => assert_eq!((path.as_str(), id), ("fields[1].source.id", 0)),
+      other => panic!("expected a duplicate id, got {:?}", other),
+    }
+    let negative_delta = valid.replace(r#""delta":1.0"#, r#""delta":-1.0"#);
+    match validate_fields(&fields(&format!("[{}]", negative_delta))) {
+      Err(Error::OutOfRange { path, .. }) => assert_eq!(path, "fields[0].delta"),
+      other => panic!("expected delta out of range, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn huge_heatmaps_are_rejected() {
+    assert!(validate_pixels(1920.0, 1080.0).is_ok());
+    assert!(validate_pixels(1e5, 1e5).is_err());
+  }
+
+  #[test]
+  fn dense_path_sampling_is_rejected() {
+    let path = [[0.0, 0.0], [1000.0, 0.0]];
+    assert!(validate_path(&path, PathSampling::Spacing(1.0)).is_ok());
+    match validate_path(&path, PathSampling::Spacing(1e-9)) {
+      Err(Error::TooManySamples { maximum, .. }) => assert_eq!(maximum, MAX_PATH_SAMPLES),
+      other => panic!("expected too many samples, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn restitution_above_one_is_rejected() {
+    let config = Config { boundary: Boundary::Reflecting { restitution: 1.5 }, ..Config::default() };
+    assert!(matches!(validate_config(&config), Err(Error::OutOfRange { .. })));
+    assert!(validate_config(&Config::default()).is_ok());
+  }
+
+  #[test]
+  fn only_points_and_dipoles_can_move() {
+    let field = |id: usize, shape: &str| format!(
+      r#"{{"source":{{"id":{},"sign":"Positive","magnitude":1.0,"position":{{"x":0.0,"y":0.0}},"r":10.0,"shape":{}}},"density":4,"steps":10,"delta":1.0}}"#,
+      id, shape
+    );
+    let moving = format!("[{},{}]", field(0, r#""Point""#), field(1, r#"{"Dipole":{"moment":{"x":1.0,"y":0.0}}}"#));
+    assert!(validate_dynamics(&fields(&moving)).is_ok());
+    let with_a_wire = format!("[{},{}]", field(0, r#""Point""#), field(1, r#"{"Segment":{"end":{"x":1.0,"y":0.0}}}"#));
+    match validate_dynamics(&fields(&with_a_wire)) {
+      Err(Error::UnsupportedShape { path, shape }) => assert_eq!((path.as_str(), shape.as_str()), ("fields[1].source.shape", "Segment")),
+      other => panic!("expected an unsupported shape, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn too_many_contour_levels_are_rejected() {
+    assert!(validate_levels(&Levels::Count(20)).is_ok());
+    assert!(matches!(validate_levels(&Levels::Count(1000000)), Err(Error::TooManyLevels { count: 1000000, .. })));
+    assert!(matches!(validate_levels(&Levels::Values(vec![0.0; 2000])), Err(Error::TooManyLevels { .. })));
+    assert!(matches!(validate_levels(&Levels::Values(vec![0.0, f64::NAN])), Err(Error::NonFinite { .. })));
+  }
+
+  #[test]
+  fn dense_boundary_seeding_is_rejected() {
+    let seeding = |spacing: f64| BoundarySeeding {
+      spacing,
+      tracing: Tracing { steps: 10, delta: 1.0, integrator: Default::default(), tolerance: 0.01 },
+    };
+    assert!(validate_seeding(&seeding(10.0), 1920.0, 1080.0).is_ok());
+    match validate_seeding(&seeding(1e-9), 1920.0, 1080.0) {
+      Err(Error::TooManySamples { path, maximum, .. }) => assert_eq!((path.as_str(), maximum), ("seeding.spacing", MAX_DENSITY)),
+      other => panic!("expected too many samples, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn huge_step_counts_are_rejected() {
+    let tracing = |steps: usize| Tracing { steps, delta: 1.0, integrator: Default::default(), tolerance: 0.01 };
+    let path = |name: &str| format!("fields[0].{}", name);
+    assert!(validate_tracing(&tracing(MAX_STEPS), &path).is_ok());
+    match validate_tracing(&tracing(usize::MAX), &path) {
+      Err(error @ Error::InvalidSteps { .. }) =>
+        assert_eq!(error.to_string(), format!("`fields[0].steps` is {} but a line needs between 1 and 100000 steps", usize::MAX)),
+      other => panic!("expected invalid steps, got {:?}", other),
+    }
+  }
+}
@@ -0,0 +1,85 @@
+use crate::error::Error;
+use crate::Point;
+
+// the share of samples at each extreme ignored when picking a value range automatically,
+// otherwise the spikes at the charges squeeze everything else into one corner of the range
+const RANGE_TRIM: f64 = 0.05;
+
+// regular lattice of `columns` x `rows` nodes spanning the whole canvas, stored row by row
+#[derive(Debug, Copy, Clone)]
+pub struct Grid {
+  pub width: f64,
+  pub height: f64,
+  pub columns: usize,
+  pub rows: usize,
+}
+
+impl Grid {
+  pub fn new(width: f64, height: f64, columns: usize, rows: usize) -> Result<Grid, Error> {
+    crate::error::validate_size(width, height)?;
+    crate::error::validate_resolution(columns, rows)?;
+    Ok(Grid { width, height, columns, rows })
+  }
+
+  pub fn point(&self, column: usize, row: usize) -> Point {
+    [ self.width * (column as f64) / ((self.columns - 1) as f64)
+    , self.height * (row as f64) / ((self.rows - 1) as f64)
+    ]
+  }
+
+  pub fn sample<T, F: Fn(Point) -> T>(&self, f: F) -> Vec<T> {
+    (0..self.rows).flat_map(|row|
+      (0..self.columns).map(move |column| (column, row))
+    ).map(|(column, row)| f(self.point(column, row))).collect()
+  }
+}
+
+// range of the finite `values` without their most extreme `RANGE_TRIM` on either side
+pub fn trimmed_range(values: &[f64]) -> Option<(f64, f64)> {
+  let mut sorted = values.iter().cloned().filter(|value| value.is_finite()).collect::<Vec<f64>>();
+  if sorted.is_empty() {
+    return None;
+  }
+  sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+  let trim = ((sorted.len() as f64) * RANGE_TRIM) as usize;
+  Some((sorted[trim], sorted[sorted.len() - 1 - trim]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nodes_span_the_canvas_row_by_row() {
+    let grid = Grid::new(300.0, 100.0, 4, 3).unwrap();
+    assert_eq!(grid.point(0, 0), [0.0, 0.0]);
+    assert_eq!(grid.point(3, 2), [300.0, 100.0]);
+    let nodes = grid.sample(|point| point);
+    assert_eq!(nodes.len(), 12);
+    assert_eq!(nodes[1], [100.0, 0.0]);
+    assert_eq!(nodes[4], [0.0, 50.0]);
+  }
+
+  #[test]
+  fn grids_need_two_nodes_along_each_side() {
+    assert!(matches!(Grid::new(300.0, 100.0, 1, 3), Err(Error::InvalidResolution { value: 1, .. })));
+    assert!(matches!(Grid::new(300.0, 100.0, 4, 0), Err(Error::InvalidResolution { value: 0, .. })));
+    assert!(Grid::new(0.0, 100.0, 4, 3).is_err());
+  }
+
+  #[test]
+  fn huge_grids_are_rejected() {
+    assert!(Grid::new(300.0, 100.0, 1024, 1024).is_ok());
+    assert!(matches!(Grid::new(300.0, 100.0, 100000, 100000), Err(Error::TooManySamples { .. })));
+    assert!(matches!(Grid::new(300.0, 100.0, usize::MAX, 2), Err(Error::TooManySamples { .. })));
+  }
+
+  #[test]
+  fn trimmed_range_skips_non_finite_values_and_the_extremes() {
+    let mut values = (0..100).map(|value| value as f64).collect::<Vec<f64>>();
+    values.push(f64::INFINITY);
+    values.push(f64::NAN);
+    assert_eq!(trimmed_range(&values), Some((5.0, 94.0)));
+    assert_eq!(trimmed_range(&[f64::NAN]), None);
+  }
+}
@@ -0,0 +1,123 @@
+use crate::grid;
+use crate::Point;
+
+#[derive(Deserialize, Debug, Copy, Clone, Default)]
+pub enum Quantity {
+  #[default]
+  FieldMagnitude,
+  Potential,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, Default)]
+pub enum Colormap {
+  #[default]
+  Viridis,
+  Inferno,
+  Grayscale,
+  // diverging blue-white-red map centered on zero, suited to potentials
+  CoolWarm,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HeatmapOptions {
+  pub quantity: Quantity,
+  pub colormap: Colormap,
+  // values mapped to the two ends of the colormap, picked from the samples when missing
+  pub range: Option<(f64, f64)>,
+  // color by sign(v) * ln(1 + |v|) instead of v
+  pub logarithmic: bool,
+}
+
+type Rgb = [u8; 3];
+
+const VIRIDIS: [Rgb; 5] = [[68, 1, 84], [59, 82, 139], [33, 145, 140], [94, 201, 98], [253, 231, 37]];
+const INFERNO: [Rgb; 5] = [[0, 0, 4], [87, 16, 110], [188, 55, 84], [249, 142, 9], [252, 255, 164]];
+const GRAYSCALE: [Rgb; 2] = [[0, 0, 0], [255, 255, 255]];
+const COOL_WARM: [Rgb; 3] = [[59, 76, 192], [221, 221, 221], [180, 4, 38]];
+
+impl Colormap {
+  fn stops(self) -> &'static [Rgb] {
+    match self {
+      Colormap::Viridis => &VIRIDIS,
+      Colormap::Inferno => &INFERNO,
+      Colormap::Grayscale => &GRAYSCALE,
+      Colormap::CoolWarm => &COOL_WARM,
+    }
+  }
+
+  fn is_diverging(self) -> bool {
+    matches!(self, Colormap::CoolWarm)
+  }
+
+  // color at `t` in [0, 1], linearly interpolated between the stops
+  fn color(self, t: f64) -> Rgb {
+    let stops = self.stops();
+    let position = t * ((stops.len() - 1) as f64);
+    let index = (position.floor() as usize).min(stops.len() - 2);
+    let fraction = position - (index as f64);
+    let [start, end] = [stops[index], stops[index + 1]];
+    [0, 1, 2].map(|channel|
+      ((start[channel] as f64) + ((end[channel] as f64) - (start[channel] as f64)) * fraction).round() as u8
+    )
+  }
+}
+
+// `f` evaluated at the center of every pixel of a `columns` x `rows` canvas, row by row
+pub fn sample_pixels<F: Fn(Point) -> f64>(columns: usize, rows: usize, f: F) -> Vec<f64> {
+  (0..rows).flat_map(|row|
+    (0..columns).map(move |column| [column as f64 + 0.5, row as f64 + 0.5])
+  ).map(f).collect()
+}
+
+// RGBA bytes for `values`, non-finite values are left transparent
+pub fn render(values: &[f64], options: &HeatmapOptions) -> Vec<u8> {
+  let scale = |value: f64|
+    if options.logarithmic { value.signum() * value.abs().ln_1p() } else { value };
+  let scaled = values.iter().map(|value| scale(*value)).collect::<Vec<f64>>();
+  let (low, high) =
+    match options.range {
+      Some((low, high)) =>
+        (scale(low), scale(high)),
+      None => {
+        let (low, high) = grid::trimmed_range(&scaled).unwrap_or((0.0, 1.0));
+        if options.colormap.is_diverging() {
+          let extent = low.abs().max(high.abs());
+          (-extent, extent)
+        } else {
+          (low, high)
+        }
+      }
+    };
+  scaled.iter().flat_map(|value| {
+    if value.is_finite() {
+      let t = if high > low { ((value - low) / (high - low)).clamp(0.0, 1.0) } else { 0.5 };
+      let [red, green, blue] = options.colormap.color(t);
+      [red, green, blue, 255]
+    } else {
+      [0, 0, 0, 0]
+    }
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn non_finite_values_are_transparent() {
+    let pixels = render(&[0.0, f64::INFINITY, 1.0], &HeatmapOptions::default());
+    assert_eq!(pixels.len(), 12);
+    assert_eq!(pixels[3], 255);
+    assert_eq!(pixels[7], 0);
+    assert_eq!(pixels[8..12], [253, 231, 37, 255]);
+  }
+
+  #[test]
+  fn diverging_maps_center_zero() {
+    let options = HeatmapOptions { colormap: Colormap::CoolWarm, ..HeatmapOptions::default() };
+    let pixels = render(&[-1.0, 0.0, 3.0], &options);
+    assert_eq!(pixels[4..8], [221, 221, 221, 255]);
+    assert_eq!(pixels[8..12], [180, 4, 38, 255]);
+  }
+}
@@ -1,16 +1,33 @@
 mod utils;
+mod error;
+mod grid;
+mod contour;
+mod arrows;
+mod heatmap;
+mod probe;
+mod sources;
+mod dynamics;
+mod boundary;
 
 extern crate vecmath;
-use itertools::Itertools;
-use itertools::FoldWhile::{Continue, Done};
 extern crate serde_json;
 extern crate console_error_panic_hook;
 use wasm_bindgen::prelude::*;
-extern crate web_sys;
+use wasm_bindgen::Clamped;
 
 #[macro_use]
 extern crate serde_derive;
 
+use error::Error;
+use grid::Grid;
+use contour::{Contour, Levels};
+use arrows::{Arrow, Scaling};
+use heatmap::{HeatmapOptions, Quantity};
+use probe::{PathSampling, Probe, ProfileSample};
+use sources::Shape;
+use dynamics::{Collision, Constraint, Diagnostics};
+use boundary::Boundary;
+
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
@@ -23,17 +40,227 @@ pub type Vector2 = vecmath::Vector2<f64>;
 struct Field {
   source: Charge,
   density: usize,
+  #[serde(flatten)]
+  tracing: Tracing,
+  // rotation in radians applied to the seed angles around the source
+  #[serde(default)]
+  angle_offset: f64,
+  // start the seed angles from the direction of the field the other charges produce at the source
+  #[serde(default)]
+  align_seeds: bool,
+  #[serde(skip_deserializing)]
+  lines: Vec<Line>,
+  // one entry per line in `lines`
+  #[serde(skip_deserializing)]
+  line_info: Vec<LineInfo>,
+}
+
+// how a single line is integrated, shared by charge seeded and boundary seeded lines
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Tracing {
   steps: usize,
   delta: f64,
-  #[serde(skip_deserializing)]
+  #[serde(default)]
+  integrator: Integrator,
+  #[serde(default = "default_tolerance")]
+  tolerance: f64,
+}
+
+// lines that enter the canvas through its edges, seeded every `spacing` pixels where the net field points inwards
+#[derive(Deserialize, Debug, Clone)]
+struct BoundarySeeding {
+  spacing: f64,
+  #[serde(flatten)]
+  tracing: Tracing,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct BoundaryLines {
   lines: Vec<Line>,
+  // one entry per line in `lines`
+  line_info: Vec<LineInfo>,
+}
+
+// result of one `step`, the moved fields and how the energy and momentum of the scene stand
+#[derive(Serialize, Debug, Clone)]
+struct Frame {
+  fields: Vec<Field>,
+  diagnostics: Diagnostics,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct LineInfo {
+  termination: Termination,
+  end_charge_id: Option<usize>,
+  arc_length: f64,
+  steps: usize,
+  // `image_offset` of the charge the line ended on, a line may reach a periodic image of it
+  #[serde(skip)]
+  end_offset: Vector2,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+enum Termination {
+  OutOfBounds,
+  HitCharge,
+  MaxSteps,
+  // the line reached a point where the net field vanishes and has no direction
+  NullField,
+  // the field could not be evaluated to a finite value
+  Singularity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
+enum Integrator {
+  #[default]
+  Euler,
+  Midpoint,
+  RungeKutta4,
+  // embedded Dormand-Prince 5(4) pair with step size controlled by `Field.tolerance`
+  RungeKutta45,
+}
+
+fn default_tolerance() -> f64 {
+  0.01
+}
+
+// adaptive steps may range from `delta / MIN_STEP_DIVISOR` up to `delta * MAX_STEP_FACTOR`
+const MIN_STEP_DIVISOR: f64 = 100.0;
+const MAX_STEP_FACTOR: f64 = 10.0;
+
+// consecutive steps turning by more than 120 degrees mean the line has crossed a null
+const REVERSAL_COSINE: f64 = -0.5;
+
+// Dormand-Prince tableau
+const DP_A: [[f64; 6]; 6] = [
+  [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+  [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+  [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+  [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+  [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+  [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+const DP_B5: [f64; 7] =
+  [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0, 0.0];
+const DP_B4: [f64; 7] =
+  [5179.0 / 57600.0, 0.0, 7571.0 / 16695.0, 393.0 / 640.0, -92097.0 / 339200.0, 187.0 / 2100.0, 1.0 / 40.0];
+
+impl Integrator {
+  // advance `point` by one step of length `h` along the unit `direction` field, for the fixed step integrators
+  fn step<F: Fn(Point) -> Vector2>(self, point: Point, h: f64, direction: F) -> Point {
+    match self {
+      Integrator::Euler =>
+        vecmath::vec2_add(point, vecmath::vec2_scale(direction(point), h)),
+      Integrator::Midpoint => {
+        let k1 = direction(point);
+        let k2 = direction(vecmath::vec2_add(point, vecmath::vec2_scale(k1, h / 2.0)));
+        vecmath::vec2_add(point, vecmath::vec2_scale(k2, h))
+      }
+      Integrator::RungeKutta4 => {
+        let k1 = direction(point);
+        let k2 = direction(vecmath::vec2_add(point, vecmath::vec2_scale(k1, h / 2.0)));
+        let k3 = direction(vecmath::vec2_add(point, vecmath::vec2_scale(k2, h / 2.0)));
+        let k4 = direction(vecmath::vec2_add(point, vecmath::vec2_scale(k3, h)));
+        let slope =
+          vecmath::vec2_add(
+            vecmath::vec2_add(k1, vecmath::vec2_scale(k2, 2.0)),
+            vecmath::vec2_add(vecmath::vec2_scale(k3, 2.0), k4)
+          );
+        vecmath::vec2_add(point, vecmath::vec2_scale(slope, h / 6.0))
+      }
+      Integrator::RungeKutta45 =>
+        unreachable!("RungeKutta45 picks its own step size, use `adaptive_step`"),
+    }
+  }
+
+  // try steps starting from `h` until the local error estimate is within `tolerance`,
+  // returning the accepted point and the step size to try next
+  fn adaptive_step<F: Fn(Point) -> Vector2>(point: Point, h: f64, delta: f64, tolerance: f64, direction: F) -> (Point, f64) {
+    let min_step = delta / MIN_STEP_DIVISOR;
+    let max_step = delta * MAX_STEP_FACTOR;
+    let mut h = h.max(min_step).min(max_step);
+    loop {
+      let (next, error) = Integrator::dormand_prince(point, h, &direction);
+      let factor =
+        if error > 0.0 {
+          (0.9 * (tolerance / error).powf(0.2)).clamp(0.2, 5.0)
+        } else {
+          5.0
+        };
+      let next_h = (h * factor).max(min_step).min(max_step);
+      if error <= tolerance || h <= min_step {
+        return (next, next_h);
+      }
+      h = next_h;
+    }
+  }
+
+  // one Dormand-Prince step, returning the 5th order point and the error estimate
+  fn dormand_prince<F: Fn(Point) -> Vector2>(point: Point, h: f64, direction: &F) -> (Point, f64) {
+    let mut k: [Vector2; 7] = [[0.0, 0.0]; 7];
+    k[0] = direction(point);
+    for stage in 0..6 {
+      let offset =
+        (0..=stage).fold([0.0, 0.0], |sum, j|
+          vecmath::vec2_add(sum, vecmath::vec2_scale(k[j], DP_A[stage][j]))
+        );
+      k[stage + 1] = direction(vecmath::vec2_add(point, vecmath::vec2_scale(offset, h)));
+    }
+    let (slope, error_slope) =
+      k.iter().enumerate().fold(([0.0, 0.0], [0.0, 0.0]), |(slope, error_slope), (i, k_i)|
+        ( vecmath::vec2_add(slope, vecmath::vec2_scale(*k_i, DP_B5[i]))
+        , vecmath::vec2_add(error_slope, vecmath::vec2_scale(*k_i, DP_B5[i] - DP_B4[i]))
+        )
+      );
+    (vecmath::vec2_add(point, vecmath::vec2_scale(slope, h)), vecmath::vec2_len(error_slope) * h)
+  }
 }
 
 type Line = Vec<Point>;
 
 type Point = Vector2;
 
+// scene-wide settings shared by every field
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+struct Config {
+  // screen pixels per meter of simulated space
+  pixels_per_meter: f64,
+  // Coulomb constant
+  k: f64,
+  // Plummer softening length in meters, keeps the field finite near charge centers
+  softening: f64,
+  // how far in pixels lines may leave the canvas before they are stopped
+  bounds_margin: f64,
+  // when set, each source seeds `|magnitude| * lines_per_unit_charge` lines instead of `Field.density`
+  lines_per_unit_charge: Option<f64>,
+  // negative sources only seed the lines that don't already arrive from positive sources
+  balance_negative_flux: bool,
+  // uniform field added to the one of the charges, as between the plates of a capacitor
+  external_field: Position,
+  // edges of the canvas for moving charges, periodic images also enter the field on the canvas
+  boundary: Boundary,
+  // how moving charges that touch react
+  collision: Collision,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Config {
+      pixels_per_meter: 100.0,
+      k: 1.0,
+      softening: 0.0,
+      bounds_margin: 100.0,
+      lines_per_unit_charge: None,
+      balance_negative_flux: false,
+      external_field: Position { x: 0.0, y: 0.0 },
+      boundary: Boundary::Open,
+      collision: Collision::PassThrough,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 struct Position {
     x: f64,
     y: f64,
@@ -52,89 +279,877 @@ struct Charge {
   magnitude: f64,
   position: Position,
   r: f64,
+  #[serde(default)]
+  shape: Shape,
+  // direction of a dipole's moment in radians, filled in for the frontend
+  #[serde(skip_deserializing)]
+  orientation: Option<f64>,
+  // pixels per second, only used by `step`
+  #[serde(default)]
+  velocity: Position,
+  #[serde(default = "default_mass")]
+  mass: f64,
+  #[serde(default)]
+  constraint: Constraint,
+  // how far a periodic image sits from the charge it copies, zero for the charge itself
+  #[serde(skip)]
+  image_offset: Vector2,
+}
+
+fn default_mass() -> f64 {
+  1.0
+}
+
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn calculate_fields( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let new_fields = trace_fields(&fields, &config, width, height);
+  Ok(JsValue::from_serde(&new_fields).unwrap())
 }
 
+// moves the charges forward by `dt` seconds under their mutual forces and traces their lines at the new positions,
+// together with the energy and momentum after the move. Only point charges and dipoles can be stepped
 #[wasm_bindgen]
-pub fn calculate_fields( width: f64, height: f64, fields_in_json: &JsValue ) -> JsValue {
-  let fields: Vec<Field> = match fields_in_json.into_serde() {
-    Ok(fields) => fields,
-    Err(err) => { web_sys::console::log_1(&format!("{:#?}", err).into()); vec![] },
+#[allow(deprecated)]
+pub fn step( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue, dt: f64 ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  error::validate_time_step(dt)?;
+  error::validate_dynamics(&fields)?;
+  let mut charges = charges(&fields);
+  dynamics::step(&mut charges, dt, &config, width, height);
+  let diagnostics = dynamics::diagnostics(&charges, &config, width, height);
+  // absorbed charges are gone from `charges` and take their fields with them
+  let moved_fields =
+    fields.into_iter().filter_map(|field|
+      charges.iter().find(|charge| charge.id == field.source.id).map(|source|
+        Field {
+          source: source.clone(),
+          ..field
+        }
+      )
+    ).collect::<Vec<Field>>();
+  let frame = Frame {
+    fields: trace_fields(&moved_fields, &config, width, height),
+    diagnostics,
   };
-  let new_fields = fields.iter().map(|field| {
-    let source_position = [field.source.position.x, field.source.position.y];
-    let delta_angle = 2.0 * std::f64::consts::PI / (field.density as f64);
-    let lines =
-      (0..field.density - 1).map(|index| {
-        let angle = delta_angle * (index as f64);
-        let dx = field.source.r * angle.cos();
-        let dy = field.source.r * angle.sin();
-        let start = vecmath::vec2_add([dx, dy], source_position);
-        calculate_field_line(
-          &fields.iter().map(|field| field.source.clone()).collect::<Vec<Charge>>(),
-          field.steps, field.delta, field.source.sign, start, width, height
-        )
-      }).collect::<Vec<Line>>();
+  Ok(JsValue::from_serde(&frame).unwrap())
+}
+
+// scalar potential at each node of a `columns` x `rows` grid over the canvas, row by row
+#[wasm_bindgen]
+pub fn calculate_potentials( width: f64, height: f64, columns: usize, rows: usize, fields_in_json: &JsValue, config_in_json: &JsValue ) -> Result<Vec<f64>, JsValue> {
+  utils::set_panic_hook();
+  let grid = Grid::new(width, height, columns, rows)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  Ok(grid.sample(|point| electric_potential(&charges, point, &config)))
+}
+
+// equipotential polylines at the requested levels, traced over a `columns` x `rows` potential grid
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn calculate_equipotentials( width: f64, height: f64, columns: usize, rows: usize, fields_in_json: &JsValue, config_in_json: &JsValue, levels_in_json: &JsValue ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  let grid = Grid::new(width, height, columns, rows)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let levels: Levels =
+    levels_in_json.into_serde()
+      .map_err(|err| Error::Parse { path: "levels".to_string(), message: err.to_string() })?;
+  error::validate_levels(&levels)?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  let potentials = grid.sample(|point| electric_potential(&charges, point, &config));
+  let contours =
+    contour::contour_levels(&levels, &potentials).into_iter().map(|level|
+      Contour {
+        level,
+        lines: contour::contour(&grid, &potentials, level),
+      }
+    ).collect::<Vec<Contour>>();
+  Ok(JsValue::from_serde(&contours).unwrap())
+}
+
+// net field at each node of a `columns` x `rows` grid over the canvas, row by row, for quiver plots
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn calculate_field_arrows( width: f64, height: f64, columns: usize, rows: usize, fields_in_json: &JsValue, config_in_json: &JsValue, scaling_in_json: &JsValue ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  let grid = Grid::new(width, height, columns, rows)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let scaling: Scaling = parse_optional(scaling_in_json, "scaling")?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  let arrows: Vec<Arrow> =
+    grid.sample(|point| arrows::arrow(point, electric_field(&charges, point, &config), scaling));
+  Ok(JsValue::from_serde(&arrows).unwrap())
+}
+
+// RGBA pixels of |E| or the potential over the canvas, `width.round()` pixels per row, for `ImageData`
+#[wasm_bindgen]
+pub fn render_heatmap( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue, options_in_json: &JsValue ) -> Result<Clamped<Vec<u8>>, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  error::validate_pixels(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let options: HeatmapOptions = parse_optional(options_in_json, "options")?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  let values =
+    heatmap::sample_pixels(width.round() as usize, height.round() as usize, |point|
+      match options.quantity {
+        Quantity::FieldMagnitude =>
+          vecmath::vec2_len(electric_field(&charges, point, &config)),
+        Quantity::Potential =>
+          electric_potential(&charges, point, &config),
+      }
+    );
+  Ok(Clamped(heatmap::render(&values, &options)))
+}
+
+// lines without a source charge, entering through the canvas edges along the net field
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn calculate_boundary_lines( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue, seeding_in_json: &JsValue ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let seeding: BoundarySeeding =
+    seeding_in_json.into_serde()
+      .map_err(|err| Error::Parse { path: "seeding".to_string(), message: err.to_string() })?;
+  error::validate_seeding(&seeding, width, height)?;
+  Ok(JsValue::from_serde(&trace_boundary_lines(&fields, &config, &seeding, width, height)).unwrap())
+}
+
+// field, potential and force on a `test_charge` at each of the given points
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn probe_points( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue, points_in_json: &JsValue, test_charge: f64 ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let points = parse_points(points_in_json, "points")?;
+  error::validate_test_charge(test_charge)?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  let probes =
+    points.into_iter().map(|point|
+      probe::probe(&charges, point, &config, test_charge)
+    ).collect::<Vec<Probe>>();
+  Ok(JsValue::from_serde(&probes).unwrap())
+}
+
+// field and potential against distance along a polyline `path`
+#[wasm_bindgen]
+#[allow(deprecated)]
+pub fn sample_path( width: f64, height: f64, fields_in_json: &JsValue, config_in_json: &JsValue, path_in_json: &JsValue, sampling_in_json: &JsValue ) -> Result<JsValue, JsValue> {
+  utils::set_panic_hook();
+  error::validate_size(width, height)?;
+  let fields = parse_fields(fields_in_json)?;
+  let config = parse_config(config_in_json)?;
+  let path = parse_points(path_in_json, "path")?;
+  let sampling: PathSampling =
+    sampling_in_json.into_serde()
+      .map_err(|err| Error::Parse { path: "sampling".to_string(), message: err.to_string() })?;
+  error::validate_path(&path, sampling)?;
+  let charges = boundary::images(&charges(&fields), config.boundary, width, height);
+  let samples: Vec<ProfileSample> = probe::profile(&charges, &path, sampling, &config);
+  Ok(JsValue::from_serde(&samples).unwrap())
+}
+
+fn charges(fields: &[Field]) -> Vec<Charge> {
+  fields.iter().map(|field| field.source.clone()).collect()
+}
+
+// fields with their lines traced from the current charge positions
+fn trace_fields(fields: &[Field], config: &Config, width: f64, height: f64) -> Vec<Field> {
+  let charges = boundary::images(&charges(fields), config.boundary, width, height);
+  let mut new_fields = fields.iter().map(|field| {
+    let (lines, line_info) =
+      match field.source.sign {
+        Sign::Negative if config.balance_negative_flux && field.source.moment_angle().is_none() =>
+          (vec![], vec![]),
+        _ => {
+          let angles = seed_angles(&charges, field, config, line_count(field, config));
+          trace_lines(&charges, field, config, angles.into_iter(), width, height)
+        }
+      };
     Field {
       lines,
+      line_info,
       ..field.clone()
     }
   }).collect::<Vec<Field>>();
-  // web_sys::console::log_1(&format!("{:#?}", new_fields).into());
-  JsValue::from_serde(&new_fields).unwrap()
+  if config.balance_negative_flux {
+    for index in 0..new_fields.len() {
+      let field = &new_fields[index];
+      // only negative charges wait for incoming lines, dipoles close their own
+      if matches!(field.source.sign, Sign::Positive) || field.source.moment_angle().is_some() {
+        continue;
+      }
+      let arrival_angles = arrival_angles(&new_fields, &field.source);
+      let missing = balanced_line_count(fields, field, config).saturating_sub(arrival_angles.len());
+      let angles =
+        if arrival_angles.is_empty() {
+          seed_angles(&charges, field, config, missing)
+        } else {
+          gap_angles(arrival_angles, missing)
+        };
+      let (lines, line_info) = trace_lines(&charges, field, config, angles.into_iter(), width, height);
+      new_fields[index].lines = lines;
+      new_fields[index].line_info = line_info;
+    }
+  }
+  new_fields
+}
+
+#[allow(deprecated)]
+fn parse_fields(fields_in_json: &JsValue) -> Result<Vec<Field>, Error> {
+  let fields: Vec<Field> =
+    fields_in_json.into_serde()
+      .map_err(|err| Error::Parse { path: "fields".to_string(), message: err.to_string() })?;
+  error::validate_fields(&fields)?;
+  let fields =
+    fields.into_iter().map(|field|
+      Field {
+        source: Charge {
+          orientation: field.source.moment_angle(),
+          ..field.source.clone()
+        },
+        ..field
+      }
+    ).collect();
+  Ok(fields)
 }
 
-fn calculate_field_line(charges: &Vec<Charge>, steps: usize, delta: f64, source_sign: Sign, start: Point, x_bound: f64, y_bound: f64) -> Line {
-  (0..steps - 1).fold_while(vec![ start ], |mut line: Line, _| {
-    let [x, y] = match line {
-      _ if line.len() > 0 =>
-        line[line.len()-1],
-      _ =>
-        [0.0, 0.0] // impossible
+#[allow(deprecated)]
+fn parse_points(points_in_json: &JsValue, path: &str) -> Result<Vec<Point>, Error> {
+  let positions: Vec<Position> =
+    points_in_json.into_serde()
+      .map_err(|err| Error::Parse { path: path.to_string(), message: err.to_string() })?;
+  error::validate_points(&positions, path)?;
+  Ok(positions.into_iter().map(|position| [position.x, position.y]).collect())
+}
+
+fn parse_config(config_in_json: &JsValue) -> Result<Config, Error> {
+  let config: Config = parse_optional(config_in_json, "config")?;
+  error::validate_config(&config)?;
+  Ok(config)
+}
+
+// a missing argument falls back to the defaults
+#[allow(deprecated)]
+fn parse_optional<T: serde::de::DeserializeOwned + Default>(value: &JsValue, path: &str) -> Result<T, Error> {
+  if value.is_undefined() || value.is_null() {
+    Ok(T::default())
+  } else {
+    value.into_serde()
+      .map_err(|err| Error::Parse { path: path.to_string(), message: err.to_string() })
+  }
+}
+
+fn trace_lines<I: Iterator<Item = f64>>(charges: &[Charge], field: &Field, config: &Config, angles: I, width: f64, height: f64) -> (Vec<Line>, Vec<LineInfo>) {
+  angles.map(|angle| {
+    let start = field.source.seed_point(angle);
+    calculate_field_line(charges, Some(&field.source), &field.tracing, config, start, width, height)
+  }).filter(|(line, _)|
+    line.iter().all(|point| is_finite(*point))
+  ).unzip()
+}
+
+// lines seeded along the canvas edges wherever the net field points into the canvas
+fn trace_boundary_lines(fields: &[Field], config: &Config, seeding: &BoundarySeeding, width: f64, height: f64) -> BoundaryLines {
+  let charges = boundary::images(&charges(fields), config.boundary, width, height);
+  let (lines, line_info) =
+    boundary_seeds(width, height, seeding.spacing).into_iter()
+      .filter(|(start, inward)|
+        vecmath::vec2_dot(electric_field(&charges, *start, config), *inward) > 0.0
+      )
+      .map(|(start, _)|
+        calculate_field_line(&charges, None, &seeding.tracing, config, start, width, height)
+      )
+      .filter(|(line, _)|
+        line.iter().all(|point| is_finite(*point))
+      )
+      .unzip();
+  BoundaryLines { lines, line_info }
+}
+
+// `count` evenly spaced angles around the outline of the source of `field`
+fn seed_angles(charges: &[Charge], field: &Field, config: &Config, count: usize) -> Vec<f64> {
+  if let Some(moment_angle) = field.source.moment_angle() {
+    // closed lines leave a dipole on the side its moment points to and return on the other,
+    // so forward lines start on the first half and backward lines on the second
+    let outgoing_angle =
+      match field.source.sign {
+        Sign::Positive => moment_angle,
+        Sign::Negative => moment_angle + std::f64::consts::PI,
+      };
+    let delta_angle = std::f64::consts::PI / (count as f64);
+    return (0..count).map(|index|
+      outgoing_angle - std::f64::consts::FRAC_PI_2 + field.angle_offset + delta_angle * (index as f64 + 0.5)
+    ).collect();
+  }
+  let base_angle =
+    if field.align_seeds {
+      let other_charges =
+        charges.iter()
+          .filter(|charge| charge.id != field.source.id)
+          .cloned()
+          .collect::<Vec<Charge>>();
+      field_direction(electric_field(&other_charges, field.source.center(), config))
+        .map_or(0.0, |[x, y]| y.atan2(x))
+    } else {
+      0.0
     };
-    let previous_position: Vector2 = [x, y];
-    let tolerance = 100.0;
-    let out_of_bounds = x > x_bound + tolerance || x < -tolerance || y > y_bound + tolerance || y < -tolerance;
+  let delta_angle = 2.0 * std::f64::consts::PI / (count as f64);
+  (0..count).map(|index| base_angle + field.angle_offset + delta_angle * (index as f64)).collect()
+}
+
+// points `spacing` apart along each edge of the canvas, paired with the edge's inward normal
+fn boundary_seeds(width: f64, height: f64, spacing: f64) -> Vec<(Point, Vector2)> {
+  let edges = [
+    ([0.0, 0.0], [width, 0.0], [0.0, 1.0]),
+    ([0.0, height], [width, height], [0.0, -1.0]),
+    ([0.0, 0.0], [0.0, height], [1.0, 0.0]),
+    ([width, 0.0], [width, height], [-1.0, 0.0]),
+  ];
+  edges.iter().flat_map(|&(from, to, inward)| {
+    let length = distance(from, to);
+    let count = (length / spacing).floor() as usize;
+    (0..count).map(move |index| {
+      let t = (spacing * (index as f64 + 0.5)) / length;
+      (vecmath::vec2_add(from, vecmath::vec2_scale(vecmath::vec2_sub(to, from), t)), inward)
+    })
+  }).collect()
+}
+
+// angles around the outline of `charge` at which lines of other fields end on it
+fn arrival_angles(fields: &[Field], charge: &Charge) -> Vec<f64> {
+  fields.iter().flat_map(|field|
+    field.lines.iter().zip(field.line_info.iter())
+  ).filter(|(_, info)|
+    info.termination == Termination::HitCharge && info.end_charge_id == Some(charge.id)
+  ).filter_map(|(line, info)|
+    line.last().map(|point| charge.outline_angle(vecmath::vec2_sub(*point, info.end_offset)))
+  ).collect()
+}
+
+// `count` new angles, each placed in the middle of the widest gap left between `taken` angles
+fn gap_angles(mut taken: Vec<f64>, count: usize) -> Vec<f64> {
+  let full_turn = 2.0 * std::f64::consts::PI;
+  let mut angles = vec![];
+  for _ in 0..count {
+    taken.sort_by(|a, b| a.rem_euclid(full_turn).partial_cmp(&b.rem_euclid(full_turn)).unwrap());
+    let (start, gap) =
+      (0..taken.len()).map(|index| {
+        let start = taken[index].rem_euclid(full_turn);
+        let end = taken[(index + 1) % taken.len()].rem_euclid(full_turn);
+        let gap = if end > start { end - start } else { end - start + full_turn };
+        (start, gap)
+      }).fold((0.0, 0.0), |widest, gap| if gap.1 > widest.1 { gap } else { widest });
+    let angle = (start + gap / 2.0).rem_euclid(full_turn);
+    angles.push(angle);
+    taken.push(angle);
+  }
+  angles
+}
+
+// number of lines seeded around the source of `field`, dipoles carry no net charge to scale by.
+// Counts derived from the magnitude are capped like `Field.density`
+fn line_count(field: &Field, config: &Config) -> usize {
+  match config.lines_per_unit_charge {
+    Some(lines_per_unit_charge) if field.source.moment_angle().is_none() =>
+      ((field.source.magnitude.abs() * lines_per_unit_charge).round() as usize).min(error::MAX_DENSITY),
+    _ =>
+      field.density,
+  }
+}
+
+// lines a negative source needs for each of them to carry as much charge as the lines of the positive
+// sources do, so a source that collects the lines of a smaller positive one still shows its remaining flux
+fn balanced_line_count(fields: &[Field], field: &Field, config: &Config) -> usize {
+  if config.lines_per_unit_charge.is_some() {
+    return line_count(field, config);
+  }
+  let (positive_lines, positive_charge) =
+    fields.iter()
+      .filter(|other| matches!(other.source.sign, Sign::Positive) && other.source.moment_angle().is_none())
+      .fold((0.0, 0.0), |(lines, charge), other|
+        (lines + line_count(other, config) as f64, charge + other.source.magnitude.abs())
+      );
+  if positive_charge > 0.0 {
+    ((field.source.magnitude.abs() * positive_lines / positive_charge).round() as usize).min(error::MAX_DENSITY)
+  } else {
+    line_count(field, config)
+  }
+}
+
+// traces a line from `start`, against the field for negative sources and along it for lines without a source
+fn calculate_field_line(charges: &[Charge], source: Option<&Charge>, tracing: &Tracing, config: &Config, start: Point, x_bound: f64, y_bound: f64) -> (Line, LineInfo) {
+  let sign = source.map_or(Sign::Positive, |source| source.sign);
+  let unit_direction = |point: Point| {
+    field_direction(electric_field(charges, point, config)).map(|unit|
+      match sign {
+        Sign::Positive =>
+          unit,
+        Sign::Negative =>
+          vecmath::vec2_neg(unit),
+      }
+    )
+  };
+  // intermediate integrator stages that land on a null contribute no direction
+  let direction = |point: Point| unit_direction(point).unwrap_or([0.0, 0.0]);
+  let mut line = vec![ start ];
+  let mut h = tracing.delta;
+  let mut termination = Termination::MaxSteps;
+  let mut end_charge_id = None;
+  let mut end_offset = [0.0, 0.0];
+  for _ in 1..tracing.steps {
+    let [x, y] = line[line.len() - 1];
+    let margin = config.bounds_margin;
+    let out_of_bounds = x > x_bound + margin || x < -margin || y > y_bound + margin || y < -margin;
     if out_of_bounds {
-      Done(line)
-    } else {
-      let net_field =
-        charges.iter().fold([0.0, 0.0], |sum, charge| {
-          let charge_position = [charge.position.x, charge.position.y];
-          let d = distance(previous_position, charge_position) / 100.0;
-          let magnitude = charge.magnitude / d.powf(2.0);
-          let sign =
-            match charge.sign {
-              Sign::Positive => 1.0,
-              Sign::Negative => -1.0
-            };
-          let field =
-            vecmath::vec2_scale(
-              vecmath::vec2_normalized(
-                vecmath::vec2_sub(previous_position, charge_position)
-            ), sign * magnitude);
-          vecmath::vec2_add(sum, field)
-        });
-      let delta_vector =
-        vecmath::vec2_scale(
-          vecmath::vec2_normalized(net_field),
-          delta
-        );
-      let next =
-        vecmath::vec2_add(
-          previous_position,
-          match source_sign {
-            Sign::Positive =>
-              delta_vector,
-            Sign::Negative =>
-              vecmath::vec2_neg(delta_vector),
+      termination = Termination::OutOfBounds;
+      break;
+    }
+    if unit_direction([x, y]).is_none() {
+      termination = Termination::NullField;
+      break;
+    }
+    let next =
+      match tracing.integrator {
+        Integrator::RungeKutta45 => {
+          let (next, next_h) = Integrator::adaptive_step([x, y], h, tracing.delta, tracing.tolerance, direction);
+          h = next_h;
+          next
+        }
+        integrator =>
+          integrator.step([x, y], tracing.delta, direction),
+      };
+    if !is_finite(next) {
+      termination = Termination::Singularity;
+      break;
+    }
+    if next == [x, y] {
+      termination = Termination::NullField;
+      break;
+    }
+    // a line that overshoots a null turns back on itself and would bounce around it until `steps` run out
+    if line.len() > 1 {
+      let previous_step = vecmath::vec2_sub([x, y], line[line.len() - 2]);
+      let step = vecmath::vec2_sub(next, [x, y]);
+      let cosine = vecmath::vec2_dot(previous_step, step) / (vecmath::vec2_len(previous_step) * vecmath::vec2_len(step));
+      if cosine < REVERSAL_COSINE {
+        line.pop();
+        termination = Termination::NullField;
+        break;
+      }
+    }
+    let entered_charge =
+      charges.iter()
+        // a dipole's lines loop back into it once they have left
+        .filter(|charge|
+          source.map(|source| source.id) != Some(charge.id) || (line.len() > 1 && charge.moment_angle().is_some())
+        )
+        .filter_map(|charge|
+          charge.entry([x, y], next).map(|t| (t, charge))
+        )
+        .fold(None, |earliest: Option<(f64, &Charge)>, entry|
+          match earliest {
+            Some(earliest) if earliest.0 <= entry.0 => Some(earliest),
+            _ => Some(entry),
           }
         );
-      line.push(next);
-      Continue(line)
+    match entered_charge {
+      Some((t, charge)) => {
+        // snap the final point onto the boundary of the charge the line runs into
+        line.push(vecmath::vec2_add([x, y], vecmath::vec2_scale(vecmath::vec2_sub(next, [x, y]), t)));
+        termination = Termination::HitCharge;
+        end_charge_id = Some(charge.id);
+        end_offset = charge.image_offset;
+        break;
+      }
+      None =>
+        line.push(next),
     }
-  }).into_inner()
+  }
+  let info = LineInfo {
+    termination,
+    end_charge_id,
+    arc_length: line.windows(2).map(|segment| distance(segment[0], segment[1])).sum(),
+    steps: line.len() - 1,
+    end_offset,
+  };
+  (line, info)
+}
+
+fn electric_field(charges: &[Charge], point: Point, config: &Config) -> Vector2 {
+  let external_field = [config.external_field.x, config.external_field.y];
+  charges.iter().fold(external_field, |sum, charge|
+    vecmath::vec2_add(sum, charge.field_at(point, config))
+  )
+}
+
+fn electric_potential(charges: &[Charge], point: Point, config: &Config) -> f64 {
+  charges.iter().map(|charge| charge.potential_at(point, config)).sum::<f64>() + external_potential(point, config)
+}
+
+// the uniform external field contributes -E·r, taking the canvas origin as its zero
+fn external_potential(point: Point, config: &Config) -> f64 {
+  let external_field = [config.external_field.x, config.external_field.y];
+  -vecmath::vec2_dot(external_field, point) / config.pixels_per_meter
+}
+
+// unit vector along `field`, or None where the direction is undefined
+fn field_direction(field: Vector2) -> Option<Vector2> {
+  let length = vecmath::vec2_len(field);
+  if length > 0.0 && length.is_finite() {
+    Some(vecmath::vec2_scale(field, 1.0 / length))
+  } else {
+    None
+  }
+}
+
+fn is_finite([x, y]: Point) -> bool {
+  x.is_finite() && y.is_finite()
 }
 
 fn distance(a: Vector2, b: Vector2) -> f64 {
   ((b[0] - a[0]).powf(2.0) + (b[1] - a[1]).powf(2.0)).sqrt()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // a source with signed charge `q` at `position`, `extra` holds any further JSON fields such as `"mass":2`
+  pub fn charge(id: usize, q: f64, [x, y]: Point, extra: &str) -> Charge {
+    let sign = if q < 0.0 { "Negative" } else { "Positive" };
+    serde_json::from_str(&format!(
+      r#"{{"id":{},"sign":"{}","magnitude":{},"position":{{"x":{},"y":{}}},"r":10.0{}}}"#,
+      id, sign, q.abs(), x, y, extra
+    )).unwrap()
+  }
+
+  fn field(source: Charge, density: usize, integrator: &str) -> Field {
+    Field {
+      source,
+      density,
+      tracing: Tracing {
+        steps: 1000,
+        delta: 2.0,
+        integrator: serde_json::from_str(&format!("\"{}\"", integrator)).unwrap(),
+        tolerance: default_tolerance(),
+      },
+      angle_offset: 0.0,
+      align_seeds: false,
+      lines: vec![],
+      line_info: vec![],
+    }
+  }
+
+  // unit tangent of the circles around the origin
+  fn around_origin([x, y]: Point) -> Vector2 {
+    let length = (x * x + y * y).sqrt();
+    [-y / length, x / length]
+  }
+
+  // how far `integrator` drifts off the unit circle in one turn of `count` steps
+  fn radius_drift(integrator: Integrator, count: usize) -> f64 {
+    let h = 2.0 * std::f64::consts::PI / (count as f64);
+    let end = (0..count).fold([1.0, 0.0], |point, _| integrator.step(point, h, around_origin));
+    (vecmath::vec2_len(end) - 1.0).abs()
+  }
+
+  #[test]
+  fn higher_order_integrators_stay_on_the_circle() {
+    let euler = radius_drift(Integrator::Euler, 100);
+    let midpoint = radius_drift(Integrator::Midpoint, 100);
+    let runge_kutta = radius_drift(Integrator::RungeKutta4, 100);
+    assert!(euler > 0.1, "euler drifted {}", euler);
+    assert!(midpoint < 1e-3, "midpoint drifted {}", midpoint);
+    assert!(runge_kutta < 1e-7, "runge kutta drifted {}", runge_kutta);
+  }
+
+  #[test]
+  fn halving_the_step_shows_the_order() {
+    // the error of a method of order p shrinks by 2^p when the step is halved
+    let ratio = |integrator: Integrator| radius_drift(integrator, 100) / radius_drift(integrator, 200);
+    assert!((ratio(Integrator::Euler) - 2.0).abs() < 0.2, "euler {}", ratio(Integrator::Euler));
+    assert!(ratio(Integrator::Midpoint) > 3.5, "midpoint {}", ratio(Integrator::Midpoint));
+    assert!(ratio(Integrator::RungeKutta4) > 14.0, "runge kutta {}", ratio(Integrator::RungeKutta4));
+  }
+
+  #[test]
+  fn euler_stays_the_default_integrator() {
+    let field: Field = serde_json::from_str(
+      r#"{"source":{"id":0,"sign":"Positive","magnitude":1.0,"position":{"x":0.0,"y":0.0},"r":10.0},"density":4,"steps":10,"delta":1.0}"#
+    ).unwrap();
+    assert!(matches!(field.tracing.integrator, Integrator::Euler));
+  }
+
+  #[test]
+  fn adaptive_step_grows_in_a_uniform_field() {
+    let (next, next_h) = Integrator::adaptive_step([0.0, 0.0], 1.0, 1.0, 0.01, |_| [1.0, 0.0]);
+    assert!(distance(next, [1.0, 0.0]) < 1e-12);
+    assert_eq!(next_h, 5.0);
+  }
+
+  #[test]
+  fn adaptive_step_shrinks_on_a_tight_curve() {
+    // a step of 1 cuts far inside the unit circle
+    let (next, next_h) = Integrator::adaptive_step([1.0, 0.0], 1.0, 1.0, 1e-6, around_origin);
+    assert!(next_h < 1.0, "next step {}", next_h);
+    assert!((vecmath::vec2_len(next) - 1.0).abs() < 1e-5, "left the circle at {:?}", next);
+  }
+
+  #[test]
+  fn lines_end_on_the_outline_of_the_charge_they_hit() {
+    let charges = vec![charge(0, 1.0, [200.0, 200.0], ""), charge(1, -1.0, [300.0, 200.0], "")];
+    let source = field(charges[0].clone(), 1, "RungeKutta4");
+    let (line, info) =
+      calculate_field_line(&charges, Some(&source.source), &source.tracing, &Config::default(), [210.0, 200.0], 500.0, 400.0);
+    assert_eq!(info.termination, Termination::HitCharge);
+    assert_eq!(info.end_charge_id, Some(1));
+    assert!((distance(*line.last().unwrap(), [300.0, 200.0]) - 10.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn line_info_describes_each_line() {
+    let fields = vec![
+      field(charge(0, 1.0, [200.0, 200.0], ""), 8, "RungeKutta4"),
+      field(charge(1, -1.0, [300.0, 200.0], ""), 8, "RungeKutta4"),
+    ];
+    let traced = trace_fields(&fields, &Config::default(), 500.0, 400.0);
+    for field in traced.iter() {
+      assert_eq!(field.lines.len(), field.line_info.len());
+      for (line, info) in field.lines.iter().zip(field.line_info.iter()) {
+        assert_eq!(info.steps, line.len() - 1);
+        let arc_length = line.windows(2).map(|segment| distance(segment[0], segment[1])).sum::<f64>();
+        assert!((info.arc_length - arc_length).abs() < 1e-9);
+        assert_eq!(info.end_charge_id.is_some(), info.termination == Termination::HitCharge);
+      }
+    }
+    // the line seeded straight at the other charge ends on it
+    assert_eq!(traced[0].line_info[0].termination, Termination::HitCharge);
+    assert_eq!(traced[0].line_info[0].end_charge_id, Some(1));
+    assert_eq!(traced[1].line_info[0].end_charge_id, None);
+  }
+
+  #[test]
+  fn line_info_tells_lines_that_run_out_of_steps_or_leave() {
+    let charges = vec![charge(0, 1.0, [200.0, 200.0], "")];
+    let mut source = field(charges[0].clone(), 1, "Euler");
+    let (_, info) =
+      calculate_field_line(&charges, Some(&source.source), &source.tracing, &Config::default(), [210.0, 200.0], 500.0, 400.0);
+    assert_eq!(info.termination, Termination::OutOfBounds);
+    assert_eq!(info.end_charge_id, None);
+    source.tracing.steps = 5;
+    let (line, info) =
+      calculate_field_line(&charges, Some(&source.source), &source.tracing, &Config::default(), [210.0, 200.0], 500.0, 400.0);
+    assert_eq!(info.termination, Termination::MaxSteps);
+    assert_eq!(info.steps, 4);
+    assert_eq!(line.len(), 5);
+    assert!((info.arc_length - 8.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn flux_line_counts_follow_the_charge() {
+    let config = Config { lines_per_unit_charge: Some(4.0), ..Config::default() };
+    assert_eq!(line_count(&field(charge(0, 2.5, [200.0, 200.0], ""), 20, "Euler"), &config), 10);
+    assert_eq!(line_count(&field(charge(0, -0.5, [200.0, 200.0], ""), 20, "Euler"), &config), 2);
+    assert_eq!(line_count(&field(charge(0, 2.5, [200.0, 200.0], ""), 20, "Euler"), &Config::default()), 20);
+  }
+
+  #[test]
+  fn flux_line_counts_are_capped() {
+    let source = field(charge(0, 1e6, [200.0, 200.0], ""), 20, "Euler");
+    let config = Config { lines_per_unit_charge: Some(1000.0), ..Config::default() };
+    assert_eq!(line_count(&source, &config), error::MAX_DENSITY);
+  }
+
+  #[test]
+  fn balanced_line_count_follows_the_charge() {
+    let fields = vec![
+      field(charge(0, 1.0, [200.0, 200.0], ""), 20, "Euler"),
+      field(charge(1, -5.0, [300.0, 200.0], ""), 20, "Euler"),
+    ];
+    let config = Config { balance_negative_flux: true, ..Config::default() };
+    assert_eq!(balanced_line_count(&fields, &fields[1], &config), 100);
+  }
+
+  #[test]
+  fn negative_charges_seed_only_the_missing_lines() {
+    let fields = vec![
+      field(charge(0, 1.0, [200.0, 200.0], ""), 20, "RungeKutta4"),
+      field(charge(1, -5.0, [300.0, 200.0], ""), 20, "RungeKutta4"),
+    ];
+    let config = Config { balance_negative_flux: true, ..Config::default() };
+    let traced = trace_fields(&fields, &config, 500.0, 400.0);
+    let arrivals = arrival_angles(&traced, &traced[1].source).len();
+    assert!(arrivals > 0);
+    assert_eq!(arrivals + traced[1].lines.len(), 100);
+  }
+
+  #[test]
+  fn gap_angles_fill_the_widest_gap() {
+    let angles = gap_angles(vec![0.0, 0.5], 1);
+    assert!((angles[0] - (0.5 + (2.0 * std::f64::consts::PI - 0.5) / 2.0)).abs() < 1e-12);
+  }
+
+  #[test]
+  fn every_density_angle_is_seeded() {
+    let fields = vec![field(charge(0, 1.0, [200.0, 200.0], ""), 7, "Euler")];
+    let traced = trace_fields(&fields, &Config::default(), 400.0, 400.0);
+    assert_eq!(traced[0].lines.len(), 7);
+    let angles = seed_angles(&charges(&fields), &fields[0], &Config::default(), 4);
+    let quarter = std::f64::consts::FRAC_PI_2;
+    assert_eq!(angles, vec![0.0, quarter, 2.0 * quarter, 3.0 * quarter]);
+  }
+
+  #[test]
+  fn angle_offset_rotates_the_seeds() {
+    let mut source = field(charge(0, 1.0, [200.0, 200.0], ""), 4, "Euler");
+    source.angle_offset = 0.25;
+    let angles = seed_angles(&charges(&[source.clone()]), &source, &Config::default(), 4);
+    assert!((angles[0] - 0.25).abs() < 1e-12);
+    assert!((angles[1] - 0.25 - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+  }
+
+  #[test]
+  fn aligned_seeds_start_along_the_field_of_the_other_charges() {
+    let mut fields = vec![
+      field(charge(0, 1.0, [200.0, 200.0], ""), 4, "Euler"),
+      field(charge(1, 1.0, [200.0, 300.0], ""), 4, "Euler"),
+    ];
+    fields[0].align_seeds = true;
+    fields[0].angle_offset = 0.1;
+    // the charge below pushes the field at the source upwards, towards -y
+    let angles = seed_angles(&charges(&fields), &fields[0], &Config::default(), 4);
+    assert!((angles[0] + std::f64::consts::FRAC_PI_2 - 0.1).abs() < 1e-12, "angles {:?}", angles);
+  }
+
+  #[test]
+  fn zero_density_seeds_no_lines() {
+    let fields = vec![field(charge(0, 1.0, [200.0, 200.0], ""), 0, "Euler")];
+    let traced = trace_fields(&fields, &Config::default(), 400.0, 400.0);
+    assert!(traced[0].lines.is_empty() && traced[0].line_info.is_empty());
+  }
+
+  #[test]
+  fn pixels_per_meter_and_k_scale_the_field() {
+    let charges = vec![charge(0, 1.0, [0.0, 0.0], "")];
+    assert_eq!(electric_field(&charges, [100.0, 0.0], &Config::default()), [1.0, 0.0]);
+    let config = Config { pixels_per_meter: 50.0, k: 3.0, ..Config::default() };
+    assert_eq!(electric_field(&charges, [100.0, 0.0], &config), [0.75, 0.0]);
+    assert_eq!(electric_potential(&charges, [100.0, 0.0], &config), 1.5);
+  }
+
+  #[test]
+  fn softening_keeps_the_center_finite() {
+    let charges = vec![charge(0, 1.0, [0.0, 0.0], "")];
+    let config = Config { softening: 1.0, ..Config::default() };
+    assert_eq!(electric_field(&charges, [0.0, 0.0], &config), [0.0, 0.0]);
+    assert_eq!(electric_potential(&charges, [0.0, 0.0], &config), 1.0);
+    // r / (r^2 + ε^2)^(3/2) with r = ε = 1 m
+    let [x, y] = electric_field(&charges, [100.0, 0.0], &config);
+    assert!((x - 2.0f64.powf(-1.5)).abs() < 1e-12 && y == 0.0);
+    assert!(electric_potential(&charges, [0.0, 0.0], &Config::default()).is_infinite());
+  }
+
+  #[test]
+  fn lines_stop_at_the_null_between_equal_charges() {
+    for integrator in ["Euler", "RungeKutta4", "RungeKutta45"].iter() {
+      let charges = vec![charge(0, 1.0, [200.0, 200.0], ""), charge(1, 1.0, [300.0, 200.0], "")];
+      let source = field(charges[0].clone(), 1, integrator);
+      let (line, info) =
+        calculate_field_line(&charges, Some(&source.source), &source.tracing, &Config::default(), [210.0, 200.0], 500.0, 400.0);
+      assert_eq!(info.termination, Termination::NullField, "{}", integrator);
+      assert!((line.last().unwrap()[0] - 250.0).abs() < 2.5, "{} ended at {:?}", integrator, line.last());
+      assert!(info.arc_length < 45.0, "{} ran {}", integrator, info.arc_length);
+    }
+  }
+
+  #[test]
+  fn lines_starting_on_a_null_stop_at_once() {
+    let charges = vec![charge(0, 1.0, [200.0, 200.0], ""), charge(1, 1.0, [300.0, 200.0], "")];
+    let source = field(charges[0].clone(), 1, "Euler");
+    let (line, info) =
+      calculate_field_line(&charges, Some(&source.source), &source.tracing, &Config::default(), [250.0, 200.0], 500.0, 400.0);
+    assert_eq!(info.termination, Termination::NullField);
+    assert_eq!(line, vec![[250.0, 200.0]]);
+  }
+
+  #[test]
+  fn dipole_lines_close_on_the_dipole() {
+    let dipole = charge(0, 1.0, [250.0, 250.0], r#","shape":{"Dipole":{"moment":{"x":1.0,"y":0.0}}}"#);
+    let traced = trace_fields(&[field(dipole, 6, "RungeKutta4")], &Config::default(), 500.0, 500.0);
+    assert_eq!(traced[0].lines.len(), 6);
+    for info in traced[0].line_info.iter() {
+      assert_eq!((info.termination, info.end_charge_id), (Termination::HitCharge, Some(0)));
+    }
+  }
+
+  #[test]
+  fn external_field_adds_a_linear_potential() {
+    let config = Config { external_field: Position { x: 5.0, y: 0.0 }, ..Config::default() };
+    assert_eq!(electric_field(&[], [100.0, 0.0], &config), [5.0, 0.0]);
+    assert!((electric_potential(&[], [100.0, 0.0], &config) + 5.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn boundary_lines_enter_where_the_field_points_inwards() {
+    let config = Config { external_field: Position { x: 1.0, y: 0.0 }, ..Config::default() };
+    let seeding = BoundarySeeding { spacing: 100.0, tracing: field(charge(0, 1.0, [0.0, 0.0], ""), 1, "Euler").tracing };
+    let boundary_lines = trace_boundary_lines(&[], &config, &seeding, 400.0, 300.0);
+    // only the left edge has the field pointing in, and a straight line crosses to the right one
+    assert_eq!(boundary_seeds(400.0, 300.0, 100.0).len(), 14);
+    assert_eq!(boundary_lines.lines.len(), 3);
+    assert_eq!(boundary_lines.line_info.len(), 3);
+    for (line, info) in boundary_lines.lines.iter().zip(boundary_lines.line_info.iter()) {
+      assert_eq!(line[0][0], 0.0);
+      assert_eq!(info.termination, Termination::OutOfBounds);
+      assert!(line.iter().all(|point| point[1] == line[0][1]));
+    }
+  }
+
+  #[test]
+  fn boundary_lines_end_on_the_charges_they_reach() {
+    let fields = vec![field(charge(0, -1.0, [200.0, 150.0], ""), 1, "RungeKutta4")];
+    let seeding = BoundarySeeding { spacing: 50.0, tracing: fields[0].tracing.clone() };
+    let boundary_lines = trace_boundary_lines(&fields, &Config::default(), &seeding, 400.0, 300.0);
+    assert_eq!(boundary_lines.lines.len(), boundary_seeds(400.0, 300.0, 50.0).len());
+    assert!(boundary_lines.line_info.iter().all(|info| info.end_charge_id == Some(0)));
+  }
+
+  #[test]
+  fn periodic_arrivals_are_measured_on_the_image() {
+    // the negative charge sits close to the right edge, so some lines reach its image left of the canvas
+    let fields = vec![
+      field(charge(0, 1.0, [100.0, 200.0], ""), 12, "RungeKutta4"),
+      field(charge(1, -1.0, [560.0, 200.0], ""), 12, "RungeKutta4"),
+    ];
+    let config = Config { boundary: Boundary::Periodic, ..Config::default() };
+    let traced = trace_fields(&fields, &config, 600.0, 400.0);
+    let through_the_edge =
+      traced[0].lines.iter().zip(traced[0].line_info.iter())
+        .filter(|(_, info)| info.end_charge_id == Some(1) && info.end_offset == [-600.0, 0.0])
+        .collect::<Vec<(&Line, &LineInfo)>>();
+    assert!(!through_the_edge.is_empty());
+    for (line, info) in through_the_edge {
+      let on_the_charge = vecmath::vec2_sub(*line.last().unwrap(), info.end_offset);
+      assert!((distance(on_the_charge, [560.0, 200.0]) - 10.0).abs() < 1e-6);
+      // they come in from the positive charge on the right of the image
+      assert!(traced[1].source.outline_angle(on_the_charge).cos() > 0.0);
+    }
+  }
+}
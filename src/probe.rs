@@ -0,0 +1,117 @@
+use crate::{Charge, Config, Point, Vector2};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Probe {
+  position: Point,
+  field: Vector2,
+  magnitude: f64,
+  potential: f64,
+  // force on the test charge placed at `position`
+  force: Vector2,
+}
+
+pub fn probe(charges: &[Charge], position: Point, config: &Config, test_charge: f64) -> Probe {
+  let field = crate::electric_field(charges, position, config);
+  Probe {
+    position,
+    field,
+    magnitude: vecmath::vec2_len(field),
+    potential: crate::electric_potential(charges, position, config),
+    force: vecmath::vec2_scale(field, test_charge),
+  }
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub enum PathSampling {
+  // this many samples spread evenly from the start to the end of the path
+  Count(usize),
+  // a sample every this many pixels along the path, plus one at its end
+  Spacing(f64),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProfileSample {
+  // arc length from the start of the path
+  distance: f64,
+  position: Point,
+  field: Vector2,
+  magnitude: f64,
+  potential: f64,
+}
+
+pub fn profile(charges: &[Charge], path: &[Point], sampling: PathSampling, config: &Config) -> Vec<ProfileSample> {
+  path_distances(path, sampling).into_iter().map(|distance| {
+    let position = point_along(path, distance);
+    let field = crate::electric_field(charges, position, config);
+    ProfileSample {
+      distance,
+      position,
+      field,
+      magnitude: vecmath::vec2_len(field),
+      potential: crate::electric_potential(charges, position, config),
+    }
+  }).collect()
+}
+
+fn path_distances(path: &[Point], sampling: PathSampling) -> Vec<f64> {
+  let length = path.windows(2).map(|segment| crate::distance(segment[0], segment[1])).sum::<f64>();
+  match sampling {
+    PathSampling::Count(count) if count < 2 =>
+      vec![0.0; count],
+    PathSampling::Count(count) =>
+      (0..count).map(|index| length * (index as f64) / ((count - 1) as f64)).collect(),
+    PathSampling::Spacing(spacing) => {
+      let mut distances =
+        (0..).map(|index| spacing * (index as f64)).take_while(|distance| *distance < length).collect::<Vec<f64>>();
+      distances.push(length);
+      distances
+    }
+  }
+}
+
+// the point `distance` along the polyline `path`
+fn point_along(path: &[Point], distance: f64) -> Point {
+  let mut remaining = distance;
+  for segment in path.windows(2) {
+    let length = crate::distance(segment[0], segment[1]);
+    if remaining <= length && length > 0.0 {
+      let direction = vecmath::vec2_sub(segment[1], segment[0]);
+      return vecmath::vec2_add(segment[0], vecmath::vec2_scale(direction, remaining / length));
+    }
+    remaining -= length;
+  }
+  path[path.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::boundary::Boundary;
+  use crate::tests::charge;
+
+  #[test]
+  fn force_is_the_field_times_the_test_charge() {
+    let probe = probe(&[charge(0, 1.0, [0.0, 0.0], "")], [100.0, 0.0], &Config::default(), -2.0);
+    assert_eq!(probe.field, [1.0, 0.0]);
+    assert_eq!(probe.force, [-2.0, 0.0]);
+    assert_eq!(probe.potential, 1.0);
+  }
+
+  #[test]
+  fn path_samples_cover_both_ends() {
+    let path = [[0.0, 0.0], [30.0, 0.0], [30.0, 40.0]];
+    assert_eq!(path_distances(&path, PathSampling::Count(3)), vec![0.0, 35.0, 70.0]);
+    assert_eq!(path_distances(&path, PathSampling::Spacing(30.0)), vec![0.0, 30.0, 60.0, 70.0]);
+    assert_eq!(point_along(&path, 50.0), [30.0, 20.0]);
+  }
+
+  #[test]
+  fn periodic_images_reach_across_the_edge() {
+    // the image of a charge 10 px inside the right edge sits 10 px outside the left one
+    let charges = crate::boundary::images(&[charge(0, 1.0, [590.0, 200.0], "")], Boundary::Periodic, 600.0, 400.0);
+    let probe = probe(&charges, [10.0, 200.0], &Config::default(), 1.0);
+    assert!(probe.field[0] > 0.0, "field {:?}", probe.field);
+    let samples = profile(&charges, &[[10.0, 200.0], [10.0, 300.0]], PathSampling::Count(2), &Config::default());
+    assert_eq!(samples[0].field, probe.field);
+  }
+}
@@ -0,0 +1,794 @@
+use std::f64::consts::PI;
+
+use crate::{Charge, Config, Point, Position, Sign, Vector2};
+
+// geometry of a source, `Charge.position` anchors it and `Charge.r` is the thickness lines start from
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum Shape {
+  #[default]
+  Point,
+  // straight wire from `position` to `end` with `magnitude` spread uniformly along it
+  Segment { end: Position },
+  // circle of `radius` around `position` with the charge spread uniformly along it
+  Ring { radius: f64 },
+  // part of a ring, from `start_angle` to `end_angle` in radians
+  Arc { radius: f64, start_angle: f64, end_angle: f64 },
+  // filled circle with the charge spread uniformly over its area
+  Disk { radius: f64 },
+  // filled polygon with `vertices` relative to `position` and the charge spread uniformly over its area
+  Polygon { vertices: Vec<Position> },
+  // ideal point dipole with the given moment, `magnitude` and `sign` don't scale it
+  Dipole { moment: Position },
+}
+
+// iterations used when searching for the point a step enters a source
+const ENTRY_ITERATIONS: usize = 50;
+
+// bounds on the samples along a step when looking for the entry into a shape that may not be convex
+const MIN_ENTRY_SAMPLES: usize = 16;
+const MAX_ENTRY_SAMPLES: usize = 1024;
+
+// bounds on the point charges an extended shape is integrated with
+const MIN_CURVE_ELEMENTS: usize = 16;
+const MAX_CURVE_ELEMENTS: usize = 360;
+const MAX_AREA_ELEMENTS: usize = 400;
+
+// points per full turn of a curved outline
+const OUTLINE_RESOLUTION: usize = 128;
+
+impl Charge {
+  pub fn center(&self) -> Point {
+    [self.position.x, self.position.y]
+  }
+
+  fn signed_magnitude(&self) -> f64 {
+    match self.sign {
+      Sign::Positive => self.magnitude,
+      Sign::Negative => -self.magnitude,
+    }
+  }
+
+  // total charge the source carries, a dipole's ends cancel
+  pub fn net_charge(&self) -> f64 {
+    match self.shape {
+      Shape::Dipole { .. } => 0.0,
+      _ => self.signed_magnitude(),
+    }
+  }
+
+  // move the whole source by `offset`, a segment's `end` is absolute so it moves along
+  pub fn translate(&mut self, [dx, dy]: Vector2) {
+    self.position.x += dx;
+    self.position.y += dy;
+    if let Shape::Segment { end } = &mut self.shape {
+      end.x += dx;
+      end.y += dy;
+    }
+  }
+
+  // direction of a dipole's moment in radians
+  pub fn moment_angle(&self) -> Option<f64> {
+    match &self.shape {
+      Shape::Dipole { moment } =>
+        Some(moment.y.atan2(moment.x)),
+      _ =>
+        None,
+    }
+  }
+
+  pub fn field_at(&self, point: Point, config: &Config) -> Vector2 {
+    let q = config.k * self.signed_magnitude();
+    match &self.shape {
+      Shape::Point =>
+        point_field(self.center(), q, point, config),
+      Shape::Dipole { moment } =>
+        dipole_field(self.center(), vecmath::vec2_scale([moment.x, moment.y], config.k), point, config),
+      Shape::Segment { end } =>
+        segment_field(self.center(), [end.x, end.y], q, point, config),
+      _ =>
+        self.elements().into_iter().fold([0.0, 0.0], |sum, (position, share)|
+          vecmath::vec2_add(sum, point_field(position, q * share, point, config))
+        ),
+    }
+  }
+
+  pub fn potential_at(&self, point: Point, config: &Config) -> f64 {
+    let q = config.k * self.signed_magnitude();
+    match &self.shape {
+      Shape::Point =>
+        point_potential(self.center(), q, point, config),
+      Shape::Dipole { moment } =>
+        dipole_potential(self.center(), vecmath::vec2_scale([moment.x, moment.y], config.k), point, config),
+      Shape::Segment { end } =>
+        segment_potential(self.center(), [end.x, end.y], q, point, config),
+      _ =>
+        self.elements().into_iter().map(|(position, share)|
+          point_potential(position, q * share, point, config)
+        ).sum(),
+    }
+  }
+
+  // fraction of the step from `a` to `b` at which it first reaches this source's outline
+  pub fn entry(&self, a: Point, b: Point) -> Option<f64> {
+    match &self.shape {
+      Shape::Point | Shape::Dipole { .. } =>
+        segment_circle_entry(a, b, self.center(), self.r),
+      Shape::Segment { end } => {
+        let (start, end) = (self.center(), [end.x, end.y]);
+        convex_entry(a, b, |point| distance_to_segment(point, start, end) - self.r)
+      }
+      Shape::Disk { radius } =>
+        segment_circle_entry(a, b, self.center(), radius + self.r),
+      Shape::Ring { .. } | Shape::Arc { .. } => {
+        let curve = self.curve();
+        sampled_entry(a, b, self.r, |point| distance_to_polyline(point, &curve) - self.r)
+      }
+      Shape::Polygon { .. } => {
+        let polygon = self.polygon();
+        sampled_entry(a, b, self.r, |point| distance_to_polygon(point, &polygon) - self.r)
+      }
+    }
+  }
+
+  // point on the outline `angle` of the way around it, a full turn being 2π
+  pub fn seed_point(&self, angle: f64) -> Point {
+    match &self.shape {
+      Shape::Point | Shape::Dipole { .. } =>
+        vecmath::vec2_add(self.center(), [self.r * angle.cos(), self.r * angle.sin()]),
+      Shape::Segment { end } =>
+        capsule_point(self.center(), [end.x, end.y], self.r, angle),
+      Shape::Disk { radius } =>
+        vecmath::vec2_add(self.center(), [(radius + self.r) * angle.cos(), (radius + self.r) * angle.sin()]),
+      _ =>
+        polyline_point(&self.outline(), angle),
+    }
+  }
+
+  // inverse of `seed_point` for a point on or near the outline
+  pub fn outline_angle(&self, point: Point) -> f64 {
+    match &self.shape {
+      Shape::Point | Shape::Disk { .. } | Shape::Dipole { .. } => {
+        let [x, y] = vecmath::vec2_sub(point, self.center());
+        y.atan2(x)
+      }
+      Shape::Segment { end } =>
+        capsule_angle(self.center(), [end.x, end.y], self.r, point),
+      _ =>
+        polyline_angle(&self.outline(), point),
+    }
+  }
+
+  // point charges approximating an extended shape, each with its share of the total charge
+  fn elements(&self) -> Vec<(Point, f64)> {
+    match &self.shape {
+      Shape::Point | Shape::Segment { .. } | Shape::Dipole { .. } =>
+        vec![(self.center(), 1.0)],
+      Shape::Ring { .. } | Shape::Arc { .. } => {
+        let (radius, start_angle, sweep) = self.arc();
+        let count =
+          ((radius * sweep.abs() / self.r.max(1.0)).ceil() as usize)
+            .clamp(MIN_CURVE_ELEMENTS, MAX_CURVE_ELEMENTS);
+        (0..count).map(|index| {
+          let angle = start_angle + sweep * (index as f64 + 0.5) / (count as f64);
+          (self.around(radius, angle), 1.0 / (count as f64))
+        }).collect()
+      }
+      Shape::Disk { radius } => {
+        // annuli of equal width, whose areas grow like 2 * ring + 1 just as their sector counts,
+        // so every sector covers the same area and carries the same share
+        let rings = ((MAX_AREA_ELEMENTS as f64 / 4.0).sqrt() as usize).max(1);
+        let count = 4 * rings * rings;
+        (0..rings).flat_map(|ring| {
+          let ring_radius = radius * (ring as f64 + 0.5) / (rings as f64);
+          let sectors = 4 * (2 * ring + 1);
+          (0..sectors).map(move |sector| {
+            let angle = 2.0 * PI * (sector as f64 + 0.5) / (sectors as f64);
+            (ring_radius, angle)
+          })
+        }).map(|(ring_radius, angle)|
+          (self.around(ring_radius, angle), 1.0 / (count as f64))
+        ).collect()
+      }
+      Shape::Polygon { .. } =>
+        polygon_elements(&self.polygon()),
+    }
+  }
+
+  fn around(&self, radius: f64, angle: f64) -> Point {
+    vecmath::vec2_add(self.center(), [radius * angle.cos(), radius * angle.sin()])
+  }
+
+  // radius, start angle and signed sweep of a ring or an arc
+  fn arc(&self) -> (f64, f64, f64) {
+    match self.shape {
+      Shape::Arc { radius, start_angle, end_angle } =>
+        (radius, start_angle, (end_angle - start_angle).clamp(-2.0 * PI, 2.0 * PI)),
+      Shape::Ring { radius } =>
+        (radius, 0.0, 2.0 * PI),
+      _ =>
+        (0.0, 0.0, 0.0),
+    }
+  }
+
+  // the charged curve of a ring or an arc
+  fn curve(&self) -> Vec<Point> {
+    let (radius, start_angle, sweep) = self.arc();
+    let count = ((OUTLINE_RESOLUTION as f64) * sweep.abs() / (2.0 * PI)).ceil().max(1.0) as usize;
+    (0..=count).map(|index|
+      self.around(radius, start_angle + sweep * (index as f64) / (count as f64))
+    ).collect()
+  }
+
+  fn polygon(&self) -> Vec<Point> {
+    match &self.shape {
+      Shape::Polygon { vertices } =>
+        vertices.iter().map(|vertex| vecmath::vec2_add(self.center(), [vertex.x, vertex.y])).collect(),
+      _ =>
+        vec![],
+    }
+  }
+
+  // closed polyline `r` outside an extended shape that lines are seeded along
+  fn outline(&self) -> Vec<Point> {
+    match &self.shape {
+      Shape::Ring { radius } => {
+        let outer = radius + self.r;
+        (0..=OUTLINE_RESOLUTION).map(|index|
+          self.around(outer, 2.0 * PI * (index as f64) / (OUTLINE_RESOLUTION as f64))
+        ).collect()
+      }
+      Shape::Arc { .. } => {
+        let (radius, start_angle, sweep) = self.arc();
+        let end_angle = start_angle + sweep;
+        let direction = sweep.signum();
+        let steps = (OUTLINE_RESOLUTION / 2).max(1);
+        let mut outline = vec![];
+        // outer side, cap at the end, inner side back and cap at the start
+        for index in 0..=steps {
+          let angle = start_angle + sweep * (index as f64) / (steps as f64);
+          outline.push(self.around(radius + self.r, angle));
+        }
+        let end = self.around(radius, end_angle);
+        let start = self.around(radius, start_angle);
+        for index in 1..steps {
+          let turn = PI * (index as f64) / (steps as f64);
+          let angle = end_angle + direction * turn;
+          outline.push(vecmath::vec2_add(end, [self.r * angle.cos(), self.r * angle.sin()]));
+        }
+        for index in 0..=steps {
+          let angle = end_angle - sweep * (index as f64) / (steps as f64);
+          outline.push(self.around((radius - self.r).max(0.0), angle));
+        }
+        for index in 1..=steps {
+          let turn = PI * (index as f64) / (steps as f64);
+          let angle = start_angle + PI + direction * turn;
+          outline.push(vecmath::vec2_add(start, [self.r * angle.cos(), self.r * angle.sin()]));
+        }
+        outline
+      }
+      Shape::Polygon { .. } =>
+        offset_polygon(&self.polygon(), self.r),
+      _ =>
+        vec![],
+    }
+  }
+}
+
+fn to_meters(vector: Vector2, config: &Config) -> Vector2 {
+  vecmath::vec2_scale(vector, 1.0 / config.pixels_per_meter)
+}
+
+fn point_field(center: Point, q: f64, point: Point, config: &Config) -> Vector2 {
+  let offset = to_meters(vecmath::vec2_sub(point, center), config);
+  let softened_distance_squared = vecmath::vec2_square_len(offset) + config.softening.powf(2.0);
+  if softened_distance_squared == 0.0 {
+    // a charge exerts no field at its own center
+    return [0.0, 0.0];
+  }
+  vecmath::vec2_scale(offset, q / softened_distance_squared.powf(1.5))
+}
+
+fn point_potential(center: Point, q: f64, point: Point, config: &Config) -> f64 {
+  let offset = to_meters(vecmath::vec2_sub(point, center), config);
+  let softened_distance_squared = vecmath::vec2_square_len(offset) + config.softening.powf(2.0);
+  // an unsoftened charge center evaluates to an infinite potential, which grid consumers skip
+  q / softened_distance_squared.sqrt()
+}
+
+// `moment` already includes the Coulomb constant
+fn dipole_field(center: Point, moment: Vector2, point: Point, config: &Config) -> Vector2 {
+  let offset = to_meters(vecmath::vec2_sub(point, center), config);
+  let softened_distance_squared = vecmath::vec2_square_len(offset) + config.softening.powf(2.0);
+  if softened_distance_squared == 0.0 {
+    return [0.0, 0.0];
+  }
+  vecmath::vec2_sub(
+    vecmath::vec2_scale(offset, 3.0 * vecmath::vec2_dot(moment, offset) / softened_distance_squared.powf(2.5)),
+    vecmath::vec2_scale(moment, 1.0 / softened_distance_squared.powf(1.5))
+  )
+}
+
+fn dipole_potential(center: Point, moment: Vector2, point: Point, config: &Config) -> f64 {
+  let offset = to_meters(vecmath::vec2_sub(point, center), config);
+  let softened_distance_squared = vecmath::vec2_square_len(offset) + config.softening.powf(2.0);
+  vecmath::vec2_dot(moment, offset) / softened_distance_squared.powf(1.5)
+}
+
+// position of `point` relative to the line through `start` and `end`:
+// the endpoints' coordinates along the line, the perpendicular distance, and the line's unit axes
+struct SegmentFrame {
+  u_start: f64,
+  u_end: f64,
+  d: f64,
+  along: Vector2,
+  across: Vector2,
+  length: f64,
+}
+
+fn segment_frame(start: Point, end: Point, point: Point, config: &Config) -> SegmentFrame {
+  let axis = to_meters(vecmath::vec2_sub(end, start), config);
+  let length = vecmath::vec2_len(axis);
+  let along = vecmath::vec2_scale(axis, 1.0 / length);
+  let offset = to_meters(vecmath::vec2_sub(point, start), config);
+  let u = vecmath::vec2_dot(offset, along);
+  let perpendicular = vecmath::vec2_sub(offset, vecmath::vec2_scale(along, u));
+  let d = vecmath::vec2_len(perpendicular);
+  let across = if d > 0.0 { vecmath::vec2_scale(perpendicular, 1.0 / d) } else { [0.0, 0.0] };
+  SegmentFrame { u_start: -u, u_end: length - u, d, along, across, length }
+}
+
+// closed form of the Coulomb integral along a uniformly charged segment
+fn segment_field(start: Point, end: Point, q: f64, point: Point, config: &Config) -> Vector2 {
+  let frame = segment_frame(start, end, point, config);
+  let lambda = q / frame.length;
+  let d = (frame.d.powf(2.0) + config.softening.powf(2.0)).sqrt();
+  let r_start = (frame.u_start.powf(2.0) + d.powf(2.0)).sqrt();
+  let r_end = (frame.u_end.powf(2.0) + d.powf(2.0)).sqrt();
+  let e_across =
+    if d > 0.0 {
+      lambda / d * (frame.u_end / r_end - frame.u_start / r_start)
+    } else {
+      // on the line itself the sideways components cancel
+      0.0
+    };
+  let e_along = lambda * (1.0 / r_end - 1.0 / r_start);
+  vecmath::vec2_add(
+    vecmath::vec2_scale(frame.across, e_across),
+    vecmath::vec2_scale(frame.along, e_along)
+  )
+}
+
+fn segment_potential(start: Point, end: Point, q: f64, point: Point, config: &Config) -> f64 {
+  let frame = segment_frame(start, end, point, config);
+  let lambda = q / frame.length;
+  let d = (frame.d.powf(2.0) + config.softening.powf(2.0)).sqrt();
+  if d > 0.0 {
+    lambda * ((frame.u_end / d).asinh() - (frame.u_start / d).asinh())
+  } else if frame.u_start * frame.u_end > 0.0 {
+    // on the extension of the segment
+    lambda * frame.u_end.signum() * (frame.u_end.abs() / frame.u_start.abs()).ln()
+  } else {
+    f64::INFINITY * lambda.signum()
+  }
+}
+
+fn distance_to_segment(point: Point, start: Point, end: Point) -> f64 {
+  let axis = vecmath::vec2_sub(end, start);
+  let length_squared = vecmath::vec2_square_len(axis);
+  let t =
+    if length_squared > 0.0 {
+      (vecmath::vec2_dot(vecmath::vec2_sub(point, start), axis) / length_squared).clamp(0.0, 1.0)
+    } else {
+      0.0
+    };
+  crate::distance(point, vecmath::vec2_add(start, vecmath::vec2_scale(axis, t)))
+}
+
+// The outline of a thick segment is a capsule, walked from `start` along one side,
+// around the cap at `end`, back along the other side and around the cap at `start`.
+fn capsule_point(start: Point, end: Point, r: f64, angle: f64) -> Point {
+  let axis = vecmath::vec2_sub(end, start);
+  let length = vecmath::vec2_len(axis);
+  let along = vecmath::vec2_scale(axis, 1.0 / length);
+  let across = [-along[1], along[0]];
+  let perimeter = 2.0 * length + 2.0 * PI * r;
+  let s = angle.rem_euclid(2.0 * PI) / (2.0 * PI) * perimeter;
+  let at = |origin: Point, u: f64, v: f64|
+    vecmath::vec2_add(origin, vecmath::vec2_add(vecmath::vec2_scale(along, u), vecmath::vec2_scale(across, v)));
+  if s < length {
+    at(start, s, r)
+  } else if s < length + PI * r {
+    let theta = (s - length) / r;
+    at(end, r * theta.sin(), r * theta.cos())
+  } else if s < 2.0 * length + PI * r {
+    at(end, -(s - length - PI * r), -r)
+  } else {
+    let theta = (s - 2.0 * length - PI * r) / r;
+    at(start, -r * theta.sin(), -r * theta.cos())
+  }
+}
+
+fn capsule_angle(start: Point, end: Point, r: f64, point: Point) -> f64 {
+  let axis = vecmath::vec2_sub(end, start);
+  let length = vecmath::vec2_len(axis);
+  let along = vecmath::vec2_scale(axis, 1.0 / length);
+  let across = [-along[1], along[0]];
+  let perimeter = 2.0 * length + 2.0 * PI * r;
+  let offset = vecmath::vec2_sub(point, start);
+  let u = vecmath::vec2_dot(offset, along);
+  let v = vecmath::vec2_dot(offset, across);
+  let s =
+    if u >= length {
+      length + r * (u - length).atan2(v).clamp(0.0, PI)
+    } else if u <= 0.0 {
+      2.0 * length + PI * r + r * (-u).atan2(-v).clamp(0.0, PI)
+    } else if v >= 0.0 {
+      u
+    } else {
+      2.0 * length + PI * r - u
+    };
+  s / perimeter * 2.0 * PI
+}
+
+fn distance_to_polyline(point: Point, polyline: &[Point]) -> f64 {
+  polyline.windows(2)
+    .map(|segment| distance_to_segment(point, segment[0], segment[1]))
+    .fold(f64::INFINITY, f64::min)
+}
+
+// distance to a filled polygon, zero inside it
+fn distance_to_polygon(point: Point, polygon: &[Point]) -> f64 {
+  if contains(polygon, point) {
+    0.0
+  } else {
+    distance_to_polyline(point, &closed(polygon))
+  }
+}
+
+// even-odd rule
+fn contains(polygon: &[Point], [x, y]: Point) -> bool {
+  let count = polygon.len();
+  (0..count).fold(false, |inside, index| {
+    let [x1, y1] = polygon[index];
+    let [x2, y2] = polygon[(index + 1) % count];
+    if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+      !inside
+    } else {
+      inside
+    }
+  })
+}
+
+fn closed(polygon: &[Point]) -> Vec<Point> {
+  polygon.iter().chain(polygon.first()).cloned().collect()
+}
+
+// twice the signed area, positive when the vertices turn counterclockwise in a y-up frame
+fn signed_double_area(polygon: &[Point]) -> f64 {
+  let count = polygon.len();
+  (0..count).map(|index| {
+    let [x1, y1] = polygon[index];
+    let [x2, y2] = polygon[(index + 1) % count];
+    x1 * y2 - x2 * y1
+  }).sum()
+}
+
+// Ear clipping triangulation, so every element lies inside the polygon even where it is concave.
+// Each triangle is split into `n * n` similar triangles represented by their centroids.
+fn polygon_elements(polygon: &[Point]) -> Vec<(Point, f64)> {
+  let triangles = triangulate(polygon);
+  let total = triangles.iter().map(|triangle| signed_double_area(triangle)).sum::<f64>();
+  let n = (((MAX_AREA_ELEMENTS / triangles.len().max(1)) as f64).sqrt() as usize).max(1);
+  triangles.into_iter().flat_map(|[a, b, c]| {
+    let share = signed_double_area(&[a, b, c]) / total / ((n * n) as f64);
+    let corner = move |i: f64, j: f64|
+      vecmath::vec2_add(a, vecmath::vec2_add(
+        vecmath::vec2_scale(vecmath::vec2_sub(b, a), i / (n as f64)),
+        vecmath::vec2_scale(vecmath::vec2_sub(c, a), j / (n as f64))
+      ));
+    let centroid = move |points: [Point; 3]|
+      vecmath::vec2_scale(vecmath::vec2_add(vecmath::vec2_add(points[0], points[1]), points[2]), 1.0 / 3.0);
+    (0..n).flat_map(move |i| (0..n - i).map(move |j| (i as f64, j as f64))).flat_map(move |(i, j)| {
+      let upright = centroid([corner(i, j), corner(i + 1.0, j), corner(i, j + 1.0)]);
+      let inverted =
+        if i + j + 2.0 <= n as f64 {
+          Some(centroid([corner(i + 1.0, j), corner(i + 1.0, j + 1.0), corner(i, j + 1.0)]))
+        } else {
+          None
+        };
+      std::iter::once(upright).chain(inverted).map(move |point| (point, share))
+    })
+  }).collect()
+}
+
+// Triangles covering a simple polygon, all turning the same way as it.
+// A corner is an ear when it turns with the polygon and no other vertex lies inside the triangle it cuts off.
+fn triangulate(polygon: &[Point]) -> Vec<[Point; 3]> {
+  let orientation = signed_double_area(polygon).signum();
+  let turn = |a: Point, b: Point, c: Point|
+    orientation * ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]));
+  let mut remaining = polygon.to_vec();
+  let mut triangles = vec![];
+  while remaining.len() > 3 {
+    let count = remaining.len();
+    let corner = |index: usize| {
+      let [a, b, c] = [remaining[(index + count - 1) % count], remaining[index], remaining[(index + 1) % count]];
+      (a, b, c)
+    };
+    let is_ear = |index: usize| {
+      let (a, b, c) = corner(index);
+      turn(a, b, c) > 0.0 &&
+        remaining.iter().all(|&point|
+          point == a || point == b || point == c ||
+            turn(a, b, point) < 0.0 || turn(b, c, point) < 0.0 || turn(c, a, point) < 0.0
+        )
+    };
+    // a polygon that crosses itself may have no ear left, its least bent corner is cut off instead
+    let index =
+      (0..count).find(|index| is_ear(*index)).unwrap_or_else(||
+        (0..count).fold(0, |best, index| {
+          let (a, b, c) = corner(index);
+          let (best_a, best_b, best_c) = corner(best);
+          if turn(a, b, c) > turn(best_a, best_b, best_c) { index } else { best }
+        })
+      );
+    let (a, b, c) = corner(index);
+    triangles.push([a, b, c]);
+    remaining.remove(index);
+  }
+  triangles.push([remaining[0], remaining[1], remaining[2]]);
+  triangles
+}
+
+// closed outline `r` outside a polygon, rounded around its convex corners
+fn offset_polygon(polygon: &[Point], r: f64) -> Vec<Point> {
+  let count = polygon.len();
+  let orientation = signed_double_area(polygon).signum();
+  let outward = |a: Point, b: Point| {
+    let [dx, dy] = vecmath::vec2_normalized(vecmath::vec2_sub(b, a));
+    vecmath::vec2_scale([dy, -dx], orientation * r)
+  };
+  let mut outline = vec![];
+  for index in 0..count {
+    let previous = polygon[(index + count - 1) % count];
+    let vertex = polygon[index];
+    let next = polygon[(index + 1) % count];
+    let [from, to] = [outward(previous, vertex), outward(vertex, next)];
+    let start_angle = from[1].atan2(from[0]);
+    let turn = (to[1].atan2(to[0]) - start_angle + PI).rem_euclid(2.0 * PI) - PI;
+    let steps = ((turn.abs() / (2.0 * PI) * OUTLINE_RESOLUTION as f64).ceil() as usize).max(1);
+    for step in 0..=steps {
+      let angle = start_angle + turn * (step as f64) / (steps as f64);
+      outline.push(vecmath::vec2_add(vertex, [r * angle.cos(), r * angle.sin()]));
+    }
+  }
+  closed(&outline)
+}
+
+// point `angle` of the way along a closed polyline, a full turn being its whole length
+fn polyline_point(polyline: &[Point], angle: f64) -> Point {
+  let length = polyline.windows(2).map(|segment| crate::distance(segment[0], segment[1])).sum::<f64>();
+  let mut remaining = angle.rem_euclid(2.0 * PI) / (2.0 * PI) * length;
+  for segment in polyline.windows(2) {
+    let segment_length = crate::distance(segment[0], segment[1]);
+    if remaining <= segment_length && segment_length > 0.0 {
+      let direction = vecmath::vec2_sub(segment[1], segment[0]);
+      return vecmath::vec2_add(segment[0], vecmath::vec2_scale(direction, remaining / segment_length));
+    }
+    remaining -= segment_length;
+  }
+  polyline[0]
+}
+
+// inverse of `polyline_point`, through the closest point on the polyline
+fn polyline_angle(polyline: &[Point], point: Point) -> f64 {
+  let length = polyline.windows(2).map(|segment| crate::distance(segment[0], segment[1])).sum::<f64>();
+  let (_, along, _) =
+    polyline.windows(2).fold((f64::INFINITY, 0.0, 0.0), |(closest, along, walked), segment| {
+      let axis = vecmath::vec2_sub(segment[1], segment[0]);
+      let segment_length = vecmath::vec2_len(axis);
+      let t =
+        if segment_length > 0.0 {
+          (vecmath::vec2_dot(vecmath::vec2_sub(point, segment[0]), axis) / segment_length.powf(2.0)).clamp(0.0, 1.0)
+        } else {
+          0.0
+        };
+      let distance = crate::distance(point, vecmath::vec2_add(segment[0], vecmath::vec2_scale(axis, t)));
+      if distance < closest {
+        (distance, walked + t * segment_length, walked + segment_length)
+      } else {
+        (closest, along, walked + segment_length)
+      }
+    });
+  along / length * 2.0 * PI
+}
+
+// fraction of the segment from `a` to `b` at which it first enters the circle, if it does
+fn segment_circle_entry(a: Point, b: Point, center: Point, r: f64) -> Option<f64> {
+  let ab = vecmath::vec2_sub(b, a);
+  let ca = vecmath::vec2_sub(a, center);
+  let qa = vecmath::vec2_dot(ab, ab);
+  let qb = 2.0 * vecmath::vec2_dot(ca, ab);
+  let qc = vecmath::vec2_dot(ca, ca) - r * r;
+  if qc <= 0.0 {
+    // already inside
+    return Some(0.0);
+  }
+  let discriminant = qb * qb - 4.0 * qa * qc;
+  if qa == 0.0 || discriminant < 0.0 {
+    return None;
+  }
+  let t = (-qb - discriminant.sqrt()) / (2.0 * qa);
+  if (0.0..=1.0).contains(&t) { Some(t) } else { None }
+}
+
+// Like `segment_circle_entry` for any convex outline given by its signed `distance`.
+// The distance to a convex shape is convex along a straight step,
+// so the closest approach is found first and the crossing is bisected before it.
+fn convex_entry<F: Fn(Point) -> f64>(a: Point, b: Point, distance: F) -> Option<f64> {
+  let at = |t: f64| distance(vecmath::vec2_add(a, vecmath::vec2_scale(vecmath::vec2_sub(b, a), t)));
+  if at(0.0) <= 0.0 {
+    return Some(0.0);
+  }
+  let (mut low, mut high) = (0.0, 1.0);
+  for _ in 0..ENTRY_ITERATIONS {
+    let third = (high - low) / 3.0;
+    if at(low + third) < at(high - third) {
+      high -= third;
+    } else {
+      low += third;
+    }
+  }
+  let closest = (low + high) / 2.0;
+  if at(closest) > 0.0 {
+    return None;
+  }
+  let (mut outside, mut inside) = (0.0, closest);
+  for _ in 0..ENTRY_ITERATIONS {
+    let middle = (outside + inside) / 2.0;
+    if at(middle) > 0.0 { outside = middle } else { inside = middle }
+  }
+  Some(inside)
+}
+
+// Like `convex_entry` for outlines that may not be convex.
+// The step is sampled closely enough not to jump over anything `thickness` wide.
+fn sampled_entry<F: Fn(Point) -> f64>(a: Point, b: Point, thickness: f64, distance: F) -> Option<f64> {
+  let at = |t: f64| distance(vecmath::vec2_add(a, vecmath::vec2_scale(vecmath::vec2_sub(b, a), t)));
+  if at(0.0) <= 0.0 {
+    return Some(0.0);
+  }
+  let samples =
+    ((crate::distance(a, b) / thickness.max(0.5)).ceil() as usize).clamp(MIN_ENTRY_SAMPLES, MAX_ENTRY_SAMPLES);
+  let crossing =
+    (1..=samples).map(|index| index as f64 / samples as f64).find(|t| at(*t) <= 0.0)?;
+  let (mut outside, mut inside) = (crossing - 1.0 / samples as f64, crossing);
+  for _ in 0..ENTRY_ITERATIONS {
+    let middle = (outside + inside) / 2.0;
+    if at(middle) > 0.0 { outside = middle } else { inside = middle }
+  }
+  Some(inside)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::charge;
+
+  fn source(shape: &str) -> Charge {
+    charge(0, 1.0, [0.0, 0.0], &format!(r#","shape":{}"#, shape))
+  }
+
+  fn relative_error(a: Vector2, b: Vector2) -> f64 {
+    vecmath::vec2_len(vecmath::vec2_sub(a, b)) / vecmath::vec2_len(b)
+  }
+
+  #[test]
+  fn disk_potential_at_center() {
+    // a uniformly charged disk has 2Q/R at its center, R = 100 px = 1 m
+    let disk = source(r#"{"Disk":{"radius":100.0}}"#);
+    let potential = disk.potential_at([0.0, 0.0], &Config::default());
+    assert!((potential - 2.0).abs() < 0.01, "potential {}", potential);
+  }
+
+  #[test]
+  fn disk_field_points_outward_inside() {
+    let disk = source(r#"{"Disk":{"radius":100.0}}"#);
+    let [x, y] = disk.field_at([50.0, 0.0], &Config::default());
+    assert!(x > 0.0, "field {:?}", [x, y]);
+    assert!(y.abs() < 1e-9, "field {:?}", [x, y]);
+  }
+
+  #[test]
+  fn steps_enter_a_point_charge_at_its_radius() {
+    let point = source(r#""Point""#);
+    let t = point.entry([-20.0, 0.0], [0.0, 0.0]).unwrap();
+    assert!((t - 0.5).abs() < 1e-12);
+    assert_eq!(point.entry([-20.0, 0.0], [-15.0, 0.0]), None);
+  }
+
+  #[test]
+  fn segment_looks_like_a_point_charge_from_far_away() {
+    let segment = source(r#"{"Segment":{"end":{"x":100.0,"y":0.0}}}"#);
+    let point = charge(1, 1.0, [50.0, 0.0], "");
+    let config = Config::default();
+    for target in [[5000.0, 3000.0], [-4000.0, 100.0], [50.0, -6000.0]].iter() {
+      let error = relative_error(segment.field_at(*target, &config), point.field_at(*target, &config));
+      assert!(error < 1e-3, "field at {:?} is off by {}", target, error);
+    }
+  }
+
+  #[test]
+  fn segment_field_matches_summed_point_charges() {
+    let segment = source(r#"{"Segment":{"end":{"x":100.0,"y":0.0}}}"#);
+    let config = Config::default();
+    let target = [30.0, 40.0];
+    let count = 2000;
+    let summed =
+      (0..count).fold([0.0, 0.0], |sum, index| {
+        let x = 100.0 * (index as f64 + 0.5) / (count as f64);
+        vecmath::vec2_add(sum, point_field([x, 0.0], 1.0 / (count as f64), target, &config))
+      });
+    let error = relative_error(segment.field_at(target, &config), summed);
+    assert!(error < 1e-4, "off by {}", error);
+  }
+
+  #[test]
+  fn polygon_close_to_a_disk_has_its_center_potential() {
+    let vertices =
+      (0..200).map(|index| {
+        let angle = 2.0 * PI * (index as f64) / 200.0;
+        format!(r#"{{"x":{},"y":{}}}"#, 100.0 * angle.cos(), 100.0 * angle.sin())
+      }).collect::<Vec<String>>().join(",");
+    let polygon = source(&format!(r#"{{"Polygon":{{"vertices":[{}]}}}}"#, vertices));
+    let potential = polygon.potential_at([0.0, 0.0], &Config::default());
+    assert!((potential - 2.0).abs() < 0.05, "potential {}", potential);
+  }
+
+  #[test]
+  fn ring_has_no_field_at_its_center() {
+    let ring = source(r#"{"Ring":{"radius":100.0}}"#);
+    assert!(vecmath::vec2_len(ring.field_at([0.0, 0.0], &Config::default())) < 1e-12);
+  }
+
+  #[test]
+  fn outline_angle_inverts_seed_point() {
+    for shape in [r#""Point""#, r#"{"Segment":{"end":{"x":100.0,"y":0.0}}}"#, r#"{"Disk":{"radius":50.0}}"#].iter() {
+      let source = source(shape);
+      for angle in [0.3, 1.7, 3.0, 4.5].iter() {
+        let back = source.outline_angle(source.seed_point(*angle));
+        let difference = (back - angle).rem_euclid(2.0 * PI);
+        assert!(difference.min(2.0 * PI - difference) < 1e-6, "{} at {} came back as {}", shape, angle, back);
+      }
+    }
+  }
+
+  #[test]
+  fn dipole_field_on_its_axis() {
+    // 2p / r^3 along the moment, r = 200 px = 2 m
+    let dipole = source(r#"{"Dipole":{"moment":{"x":1.0,"y":0.0}}}"#);
+    let [x, y] = dipole.field_at([200.0, 0.0], &Config::default());
+    assert!((x - 0.25).abs() < 1e-12 && y.abs() < 1e-12, "field {:?}", [x, y]);
+    assert_eq!(dipole.net_charge(), 0.0);
+  }
+
+  #[test]
+  fn concave_polygon_matches_its_filled_area() {
+    let vertices = [[200.0, -100.0], [-100.0, -100.0], [-100.0, 100.0], [200.0, 100.0], [200.0, 60.0], [-60.0, 60.0], [-60.0, -60.0], [200.0, -60.0]];
+    let json = vertices.iter().map(|[x, y]| format!(r#"{{"x":{},"y":{}}}"#, x, y)).collect::<Vec<String>>().join(",");
+    let polygon = source(&format!(r#"{{"Polygon":{{"vertices":[{}]}}}}"#, json));
+    let config = Config::default();
+    // point charges on a fine grid over the polygon's inside
+    let inside =
+      (0..300).flat_map(|column| (0..200).map(move |row| [-99.5 + column as f64, -99.5 + row as f64]))
+        .filter(|point| contains(&polygon.polygon(), *point))
+        .collect::<Vec<Point>>();
+    let share = 1.0 / (inside.len() as f64);
+    for target in [[100.0, 0.0], [0.0, 0.0], [-300.0, 50.0], [150.0, 250.0]].iter() {
+      let reference =
+        inside.iter().fold([0.0, 0.0], |sum, point| vecmath::vec2_add(sum, point_field(*point, share, *target, &config)));
+      let error = relative_error(polygon.field_at(*target, &config), reference);
+      assert!(error < 0.02, "field at {:?} is off by {}", target, error);
+    }
+  }
+}
@@ -6466,18 +6466,27 @@ var $author$project$Simulation$decodeCharge = A3(
 							$author$project$Simulation$decodeVector2,
 							function (position) {
 								return A3(
-									$webbhuset$elm_json_decode$Json$Decode$Field$require,
-									'r',
-									$elm$json$Json$Decode$float,
-									function (r) {
-										return $elm$json$Json$Decode$succeed(
-											{
-												id: id,
-												magnitude: magnitude,
-												position: position,
-												r: r,
-												sign: sign,
-												velocity: A2($elm_explorations$linear_algebra$Math$Vector2$vec2, 0, 0)
+									$webbhuset$elm_json_decode$Json$Decode$Field$attempt,
+									'velocity',
+									$author$project$Simulation$decodeVector2,
+									function (velocity) {
+										return A3(
+											$webbhuset$elm_json_decode$Json$Decode$Field$require,
+											'r',
+											$elm$json$Json$Decode$float,
+											function (r) {
+												return $elm$json$Json$Decode$succeed(
+													{
+														id: id,
+														magnitude: magnitude,
+														position: position,
+														r: r,
+														sign: sign,
+														velocity: A2(
+															$elm$core$Maybe$withDefault,
+															A2($elm_explorations$linear_algebra$Math$Vector2$vec2, 0, 0),
+															velocity)
+													});
 											});
 									});
 							});
@@ -6820,6 +6829,7 @@ var $author$project$Simulation$encodeCharge = function (_v0) {
 	var sign = _v0.sign;
 	var magnitude = _v0.magnitude;
 	var position = _v0.position;
+	var velocity = _v0.velocity;
 	var r = _v0.r;
 	return $elm$json$Json$Encode$object(
 		_List_fromArray(
@@ -6837,6 +6847,9 @@ var $author$project$Simulation$encodeCharge = function (_v0) {
 				'position',
 				$author$project$Simulation$encodeVector2(position)),
 				_Utils_Tuple2(
+				'velocity',
+				$author$project$Simulation$encodeVector2(velocity)),
+				_Utils_Tuple2(
 				'r',
 				$elm$json$Json$Encode$float(r))
 			]));
@@ -8998,100 +9011,60 @@ var $elm_explorations$linear_algebra$Math$Vector2$normalize = _MJS_v2normalize;
 var $elm$core$Basics$pow = _Basics_pow;
 var $elm_explorations$linear_algebra$Math$Vector2$scale = _MJS_v2scale;
 var $elm_explorations$linear_algebra$Math$Vector2$sub = _MJS_v2sub;
-var $author$project$Simulation$calculateElectricForce = F2(
-	function (self, rests) {
-		return A3(
-			$elm$core$List$foldl,
-			F2(
-				function (other, netForce) {
-					var sign = function () {
-						var _v0 = _Utils_Tuple2(self.source.sign, other.source.sign);
-						_v0$2:
-						while (true) {
-							if (_v0.a.$ === 'Positive') {
-								if (_v0.b.$ === 'Negative') {
-									var _v1 = _v0.a;
-									var _v2 = _v0.b;
-									return -1;
-								} else {
-									break _v0$2;
-								}
-							} else {
-								if (_v0.b.$ === 'Positive') {
-									var _v3 = _v0.a;
-									var _v4 = _v0.b;
-									return -1;
-								} else {
-									break _v0$2;
-								}
-							}
-						}
-						return 1;
-					}();
-					var d = A2($elm_explorations$linear_algebra$Math$Vector2$distance, self.source.position, other.source.position) / 100;
-					var forceDirection = (!d) ? self.source.velocity : $elm_explorations$linear_algebra$Math$Vector2$normalize(
-						A2($elm_explorations$linear_algebra$Math$Vector2$sub, self.source.position, other.source.position));
-					var forceMagnitude = (!d) ? 0.5 : A2(
-						$elm$core$Basics$min,
-						2,
-						(self.source.magnitude * other.source.magnitude) / A2($elm$core$Basics$pow, d, 2));
-					var force = A2($elm_explorations$linear_algebra$Math$Vector2$scale, sign * forceMagnitude, forceDirection);
-					return A2($elm_explorations$linear_algebra$Math$Vector2$add, force, netForce);
-				}),
-			A2($elm_explorations$linear_algebra$Math$Vector2$vec2, 0, 0),
-			rests);
+var $author$project$Simulation$dynamicsConfig = $elm$json$Json$Encode$object(
+	_List_fromArray(
+		[
+			_Utils_Tuple2(
+			'boundary',
+			$elm$json$Json$Encode$object(
+				_List_fromArray(
+					[
+						_Utils_Tuple2(
+						'Reflecting',
+						$elm$json$Json$Encode$object(
+							_List_fromArray(
+								[
+									_Utils_Tuple2(
+									'restitution',
+									$elm$json$Json$Encode$float(1))
+								])))
+					])))
+		]));
+var $author$project$Simulation$maxTimeStep = 0.05;
+var $author$project$Simulation$stepPort = _Platform_outgoingPort('stepPort', $elm$core$Basics$identity);
+var $author$project$Simulation$stepFields = F4(
+	function (width, height, dt, fields) {
+		return $author$project$Simulation$stepPort(
+			$elm$json$Json$Encode$object(
+				_List_fromArray(
+					[
+						_Utils_Tuple2(
+						'width',
+						$elm$json$Json$Encode$float(width)),
+						_Utils_Tuple2(
+						'height',
+						$elm$json$Json$Encode$float(height)),
+						_Utils_Tuple2(
+						'fields',
+						A2($elm$json$Json$Encode$list, $author$project$Simulation$encodeField, fields)),
+						_Utils_Tuple2('config', $author$project$Simulation$dynamicsConfig),
+						_Utils_Tuple2(
+						'dt',
+						$elm$json$Json$Encode$float(dt))
+					])));
 	});
-var $elm_explorations$linear_algebra$Math$Vector2$fromRecord = _MJS_v2fromRecord;
-var $elm_explorations$linear_algebra$Math$Vector2$toRecord = _MJS_v2toRecord;
-var $author$project$Simulation$move = function (model) {
-	var fields = model.fields;
-	var newFields = A2(
-		$elm$core$List$map,
-		function (field) {
-			var width = model.width;
-			var source = field.source;
-			var r = field.source.r;
-			var height = model.height;
-			var force = A2(
-				$author$project$Simulation$calculateElectricForce,
-				field,
-				A2(
-					$elm$core$List$filter,
-					$elm$core$Basics$neq(field),
-					fields));
-			var newAcceleration = force;
-			var newVelocity = $elm_explorations$linear_algebra$Math$Vector2$toRecord(
-				A2($elm_explorations$linear_algebra$Math$Vector2$add, newAcceleration, field.source.velocity));
-			var newPosition = $elm_explorations$linear_algebra$Math$Vector2$toRecord(
-				A2(
-					$elm_explorations$linear_algebra$Math$Vector2$add,
-					$elm_explorations$linear_algebra$Math$Vector2$fromRecord(newVelocity),
-					field.source.position));
-			var newX = (_Utils_cmp(newPosition.x, r) < 0) ? r : ((_Utils_cmp(newPosition.x, width - r) > 0) ? (width - r) : newPosition.x);
-			var newY = (_Utils_cmp(newPosition.y, r) < 0) ? r : ((_Utils_cmp(newPosition.y, height - r) > 0) ? (height - r) : newPosition.y);
-			var newXVelocity = ((_Utils_cmp(newPosition.x, r) < 0) || (_Utils_cmp(newPosition.x, width - r) > 0)) ? ((-1) * newVelocity.x) : newVelocity.x;
-			var newYVelocity = ((_Utils_cmp(newPosition.y, r) < 0) || (_Utils_cmp(newPosition.y, height - r) > 0)) ? ((-1) * newVelocity.y) : newVelocity.y;
-			return _Utils_update(
-				field,
-				{
-					source: _Utils_update(
-						source,
-						{
-							position: A2($elm_explorations$linear_algebra$Math$Vector2$vec2, newX, newY),
-							velocity: A2($elm_explorations$linear_algebra$Math$Vector2$vec2, newXVelocity, newYVelocity)
-						})
-				});
-		},
-		fields);
-	return _Utils_Tuple2(
-		model,
-		A3($author$project$Simulation$calculateFields, model.width, model.height, newFields));
-};
 var $author$project$Simulation$step = F2(
-	function (_v0, model) {
-		var _v1 = model.state;
-		if (_v1.$ === 'Running') {
-			return $author$project$Simulation$move(model);
+	function (delta, model) {
+		var _v0 = model.state;
+		if (_v0.$ === 'Running') {
+			return _Utils_Tuple2(
+				model,
+				A4(
+					$author$project$Simulation$stepFields,
+					model.width,
+					model.height,
+					A2($elm$core$Basics$min, $author$project$Simulation$maxTimeStep, delta / 1000),
+					model.fields));
 		} else {
 			return _Utils_Tuple2(model, $elm$core$Platform$Cmd$none);
 		}
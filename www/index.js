@@ -53,7 +53,22 @@ app.ports.saveProject.subscribe(function(project) {
 
 // calculate fields
 app.ports.calculateFieldsPort.subscribe(function([width, height, fields_in_json]) {
-  app.ports.receiveFieldsPort.send(wasm.calculate_fields(width, height, fields_in_json));
+  try {
+    app.ports.receiveFieldsPort.send(wasm.calculate_fields(width, height, fields_in_json));
+  } catch (error) {
+    // keep the previous lines on screen instead of wiping them
+    console.error(error.message, error);
+  }
+});
+
+// move charges and retrace their lines
+app.ports.stepPort.subscribe(function({width, height, fields, config, dt}) {
+  try {
+    var frame = wasm.step(width, height, fields, config, dt);
+    app.ports.receiveFieldsPort.send(frame.fields);
+  } catch (error) {
+    console.error(error.message, error);
+  }
 });
 
 window.addEventListener("beforeunload", function() {
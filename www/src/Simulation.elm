@@ -27,6 +27,7 @@ import Utils exposing (styles)
 
 
 port calculateFieldsPort : (Float, Float, Encode.Value) -> Cmd msg
+port stepPort : Encode.Value -> Cmd msg
 port receiveFieldsPort : (Encode.Value -> msg) -> Sub msg 
 
 
@@ -35,6 +36,36 @@ calculateFields width height fields =
   calculateFieldsPort (width, height, Encode.list encodeField fields)
 
 
+-- move the charges `dt` seconds forward in Rust, the moved fields come back through `receiveFieldsPort`
+stepFields : Float -> Float -> Float -> List Field -> Cmd msg
+stepFields width height dt fields =
+  stepPort <|
+    Encode.object
+      [ ("width", Encode.float width)
+      , ("height", Encode.float height)
+      , ("fields", Encode.list encodeField fields)
+      , ("config", dynamicsConfig)
+      , ("dt", Encode.float dt)
+      ]
+
+
+-- charges bounce off the edges of the canvas without losing speed
+dynamicsConfig : Encode.Value
+dynamicsConfig =
+  Encode.object
+    [ ("boundary"
+      , Encode.object
+        [ ("Reflecting", Encode.object [ ("restitution", Encode.float 1) ]) ]
+      )
+    ]
+
+
+-- longest step in seconds, so a frame arriving after a long pause doesn't fling the charges apart
+maxTimeStep : Float
+maxTimeStep =
+  0.05
+
+
 type alias Model =
   { name : String
   , fields : List Field
@@ -412,12 +443,13 @@ encodeVector2 vec =
     ]
 
 encodeCharge : Charge -> Encode.Value
-encodeCharge { id, sign, magnitude, position, r } =
+encodeCharge { id, sign, magnitude, position, velocity, r } =
   Encode.object
     [ ("id", Encode.int id)
     , ("sign", encodeSign sign)
     , ("magnitude", Encode.float magnitude)
     , ("position", encodeVector2 position)
+    , ("velocity", encodeVector2 velocity)
     , ("r", Encode.float r)
     ]
 
@@ -536,6 +568,7 @@ decodeCharge =
   Field.require "sign" decodeSign <| \sign ->
   Field.require "magnitude" Decode.float <| \magnitude ->
   Field.require "position" decodeVector2 <| \position ->
+  Field.attempt "velocity" decodeVector2 <| \velocity ->
   Field.require "r" Decode.float <| \r ->
 
   Decode.succeed
@@ -543,7 +576,7 @@ decodeCharge =
     , sign = sign
     , magnitude = magnitude
     , position = position
-    , velocity = vec2 0 0
+    , velocity = Maybe.withDefault (vec2 0 0) velocity
     , r = r
     }
 
@@ -748,118 +781,15 @@ receivedFields fieldsInJson model =
 
 
 step : Float -> Model -> (Model, Cmd Msg)
-step _ model =
+step delta model =
   case model.state of
     Running ->
-      move model
+      ( model
+      , stepFields model.width model.height (min maxTimeStep (delta / 1000)) model.fields
+      )
     Resting ->
       (model, Cmd.none)
 
-move : Model -> (Model, Cmd Msg)
-move model =
-  let
-    fields =
-      model.fields
-    newFields =
-      List.map
-        (\field ->
-          let
-            force =
-              calculateElectricForce field (List.filter ((/=) field) fields)
-            newAcceleration =
-              force
-            newVelocity =
-              Vector2.toRecord <| Vector2.add newAcceleration field.source.velocity
-            newPosition =
-              Vector2.toRecord <| Vector2.add (Vector2.fromRecord newVelocity) field.source.position
-            r =
-              field.source.r
-            width =
-              model.width
-            height =
-              model.height
-            newX =
-              if newPosition.x < r then
-                r
-              else if newPosition.x > width - r then
-                width - r
-              else
-                newPosition.x
-            
-            newY =
-              if newPosition.y < r then
-                r
-              else if newPosition.y > height - r then
-                height - r
-              else
-                newPosition.y
-
-            newXVelocity =
-              if newPosition.x < r || newPosition.x > width - r then
-                -1 * newVelocity.x
-              else
-                newVelocity.x
-            
-            newYVelocity =
-              if newPosition.y < r || newPosition.y > height - r then
-                -1 * newVelocity.y
-              else
-                newVelocity.y
-            
-            source =
-              field.source
-          in
-          { field
-            | source =
-              { source
-                | position =
-                  vec2 newX newY
-                , velocity =
-                  vec2 newXVelocity newYVelocity
-              }
-          }
-        )
-        fields
-  in
-  ( model
-  , calculateFields model.width model.height newFields
-  )
-
-
-calculateElectricForce : Field -> List Field -> Vec2
-calculateElectricForce self rests =
-  List.foldl
-    (\other netForce ->
-      let
-        d =
-          Vector2.distance self.source.position other.source.position / 100
-        forceMagnitude =
-          if d == 0 then
-            0.5
-          else
-            min 2 <| self.source.magnitude * other.source.magnitude / d ^ 2
-        forceDirection =
-          if d == 0 then
-            self.source.velocity
-          else
-            Vector2.normalize <| Vector2.sub self.source.position other.source.position
-        force =
-          Vector2.scale (sign * forceMagnitude) forceDirection
-        sign =
-          case (self.source.sign, other.source.sign) of
-            (Positive, Negative) ->
-              -1
-            (Negative, Positive) ->
-              -1
-            _ ->
-              1
-      in
-      Vector2.add force netForce
-    )
-    (Vector2.vec2 0 0)
-    rests
-
-
 
 duplicateActiveField : Model -> (Model, Cmd Msg)
 duplicateActiveField model =